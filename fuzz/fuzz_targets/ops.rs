@@ -0,0 +1,22 @@
+//! Drives the operation model from `tests/common/model.rs` with fuzzer input.
+//!
+//! `cargo +nightly fuzz run ops`
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../tests/common/model.rs"]
+mod model;
+
+use model::{run, Op, Strategy};
+
+fuzz_target!(|input: (u8, Strategy, u8, Vec<Op>)| {
+    let (stack, strategy, initial, ops) = input;
+    match stack % 4 {
+        0 => run::<0>(strategy, initial, &ops),
+        1 => run::<1>(strategy, initial, &ops),
+        2 => run::<4>(strategy, initial, &ops),
+        _ => run::<16>(strategy, initial, &ops),
+    }
+});
@@ -0,0 +1,273 @@
+use crate::SmartBuffer;
+use crate::storage::Storage;
+
+use core::cmp::Ordering;
+
+/// Raw pointers to the stack and heap, so that two slots can be borrowed mutably at once.
+struct RawSlots<T>{
+    stack: *mut T,
+    heap: *mut T,
+    inline: usize,
+}
+
+impl<T> RawSlots<T>{
+    /// Pointer to the slot at `index`, which must be below the capacity.
+    unsafe fn at(&self, index: usize) -> *mut T{
+        if index < self.inline{
+            return self.stack.add(index)
+        }
+        self.heap.add(index - self.inline)
+    }
+}
+
+impl<T, const N:usize, S> SmartBuffer<T,N,S>
+    where T: Clone,
+        S: Storage<T>
+{
+    fn raw_slots(&mut self) -> RawSlots<T>{
+        RawSlots{
+            stack: self.s_buf.as_mut_ptr(),
+            heap: self.d_buf.unwrap_or(core::ptr::null_mut()),
+            inline: self.inline,
+        }
+    }
+
+    /// Reverses the order of the elements.
+    pub fn reverse(&mut self){
+        self.record_slots(0..self.size);
+        match self.as_mut_slices(){
+            (slice, []) | ([], slice) => slice.reverse(),
+            _ => {
+                for i in 0..self.size / 2{
+                    self.swap_slots(i, self.size - 1 - i);
+                }
+            }
+        }
+    }
+
+    /// Sorts the elements with a stable sort.
+    ///
+    /// The stack and heap parts are sorted as slices, then merged in place across the seam.
+    pub fn sort(&mut self)
+    where T: Ord
+    {
+        self.sort_by(T::cmp);
+    }
+
+    /// Sorts the elements with a stable sort using a comparator function.
+    pub fn sort_by<F>(&mut self, mut compare: F)
+    where F: FnMut(&T, &T) -> Ordering
+    {
+        self.record_slots(0..self.size);
+        #[cfg(feature = "alloc")]
+        {
+            let (stack, heap) = self.as_mut_slices();
+            stack.sort_by(&mut compare);
+            heap.sort_by(&mut compare);
+            let mid = stack.len();
+            self.merge(0, mid, self.size, &mut |a, b| compare(a, b) == Ordering::Less);
+        }
+        // the stable slice sort needs a scratch allocation
+        #[cfg(not(feature = "alloc"))]
+        self.merge_sort(0, self.size, &mut |a, b| compare(a, b) == Ordering::Less);
+    }
+
+    /// Sorts the elements with a stable sort using a key extraction function.
+    pub fn sort_by_key<K, F>(&mut self, mut f: F)
+    where F: FnMut(&T) -> K,
+        K: Ord
+    {
+        self.sort_by(|a, b| f(a).cmp(&f(b)));
+    }
+
+    /// Sorts the elements without preserving the order of equal elements.
+    pub fn sort_unstable(&mut self)
+    where T: Ord
+    {
+        self.sort_unstable_by(T::cmp);
+    }
+
+    /// Sorts the elements using a comparator function, without preserving the order of equal elements.
+    pub fn sort_unstable_by<F>(&mut self, mut compare: F)
+    where F: FnMut(&T, &T) -> Ordering
+    {
+        self.record_slots(0..self.size);
+        let (stack, heap) = self.as_mut_slices();
+        stack.sort_unstable_by(&mut compare);
+        heap.sort_unstable_by(&mut compare);
+        let mid = stack.len();
+        self.merge(0, mid, self.size, &mut |a, b| compare(a, b) == Ordering::Less);
+    }
+
+    /// Sorts the elements using a key extraction function, without preserving the order of equal elements.
+    pub fn sort_unstable_by_key<K, F>(&mut self, mut f: F)
+    where F: FnMut(&T) -> K,
+        K: Ord
+    {
+        self.sort_unstable_by(|a, b| f(a).cmp(&f(b)));
+    }
+
+    /// Stable in-place merge sort of `lo..hi`, which needs no scratch memory.
+    #[cfg(not(feature = "alloc"))]
+    fn merge_sort<F>(&mut self, lo: usize, hi: usize, is_less: &mut F)
+    where F: FnMut(&T, &T) -> bool
+    {
+        if hi - lo <= 16{
+            for i in (lo + 1)..hi{
+                let mut j = i;
+                while j > lo && unsafe {is_less(self.get_unchecked(j), self.get_unchecked(j - 1))}{
+                    self.swap_slots(j, j - 1);
+                    j -= 1;
+                }
+            }
+            return
+        }
+        let mid = lo + (hi - lo) / 2;
+        self.merge_sort(lo, mid, is_less);
+        self.merge_sort(mid, hi, is_less);
+        self.merge(lo, mid, hi, is_less);
+    }
+
+    /// Stably merges the sorted runs `lo..mid` and `mid..hi` in place by rotating them into each other.
+    fn merge<F>(&mut self, lo: usize, mid: usize, hi: usize, is_less: &mut F)
+    where F: FnMut(&T, &T) -> bool
+    {
+        if lo == mid || mid == hi || !unsafe {is_less(self.get_unchecked(mid), self.get_unchecked(mid - 1))}{
+            return
+        }
+        if hi - lo == 2{
+            self.swap_slots(lo, mid);
+            return
+        }
+
+        let (cut1, cut2);
+        if mid - lo > hi - mid{
+            cut1 = lo + (mid - lo) / 2;
+            // first element of the right run that is not less than the cut
+            cut2 = self.partition_point_in(mid, hi, |elem, this| is_less(elem, unsafe {this.get_unchecked(cut1)}));
+        } else {
+            cut2 = mid + (hi - mid) / 2;
+            // first element of the left run that is greater than the cut
+            cut1 = self.partition_point_in(lo, mid, |elem, this| !is_less(unsafe {this.get_unchecked(cut2)}, elem));
+        }
+
+        self.rotate(cut1, mid, cut2);
+        let new_mid = cut1 + (cut2 - mid);
+        self.merge(lo, cut1, new_mid, is_less);
+        self.merge(new_mid, cut2, hi, is_less);
+    }
+
+    /// Binary search for the first index in `lo..hi` where `pred` turns false.
+    fn partition_point_in<P>(&self, mut lo: usize, mut hi: usize, mut pred: P) -> usize
+    where P: FnMut(&T, &Self) -> bool
+    {
+        while lo < hi{
+            let mid = lo + (hi - lo) / 2;
+            if pred(unsafe {self.get_unchecked(mid)}, self){
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+
+    /// Rotates `lo..hi` so that the element at `mid` ends up at `lo`.
+    fn rotate(&mut self, lo: usize, mid: usize, hi: usize){
+        self.reverse_range(lo, mid);
+        self.reverse_range(mid, hi);
+        self.reverse_range(lo, hi);
+    }
+
+    fn reverse_range(&mut self, mut lo: usize, mut hi: usize){
+        while lo + 1 < hi{
+            hi -= 1;
+            self.swap_slots(lo, hi);
+            lo += 1;
+        }
+    }
+
+    /// Binary searches the sorted SmartBuffer for `x`, see [`slice::binary_search`].
+    pub fn binary_search(&self, x: &T) -> Result<usize, usize>
+    where T: Ord
+    {
+        self.binary_search_by(|elem| elem.cmp(x))
+    }
+
+    /// Binary searches the sorted SmartBuffer with a comparator function, see [`slice::binary_search_by`].
+    pub fn binary_search_by<F>(&self, mut f: F) -> Result<usize, usize>
+    where F: FnMut(&T) -> Ordering
+    {
+        let mut lo = 0;
+        let mut hi = self.size;
+        while lo < hi{
+            let mid = lo + (hi - lo) / 2;
+            match f(unsafe {self.get_unchecked(mid)}){
+                Ordering::Less => lo = mid + 1,
+                Ordering::Greater => hi = mid,
+                Ordering::Equal => return Ok(mid),
+            }
+        }
+        Err(lo)
+    }
+
+    /// Binary searches the sorted SmartBuffer with a key extraction function, see [`slice::binary_search_by_key`].
+    pub fn binary_search_by_key<K, F>(&self, key: &K, mut f: F) -> Result<usize, usize>
+    where F: FnMut(&T) -> K,
+        K: Ord
+    {
+        self.binary_search_by(|elem| f(elem).cmp(key))
+    }
+
+    /// Returns true if the SmartBuffer contains an element equal to `x`.
+    pub fn contains(&self, x: &T) -> bool
+    where T: PartialEq
+    {
+        self.iter().any(|elem| elem == x)
+    }
+
+    /// Returns the index of the first element matching `pred`.
+    pub fn position<P>(&self, pred: P) -> Option<usize>
+    where P: FnMut(&T) -> bool
+    {
+        self.iter().position(pred)
+    }
+
+    /// Removes consecutive repeated elements, like `Vec::dedup`.
+    pub fn dedup(&mut self)
+    where T: PartialEq
+    {
+        self.dedup_by(|a, b| a == b);
+    }
+
+    /// Removes consecutive elements that map to the same key.
+    pub fn dedup_by_key<K, F>(&mut self, mut key: F)
+    where F: FnMut(&mut T) -> K,
+        K: PartialEq
+    {
+        self.dedup_by(|a, b| key(a) == key(b));
+    }
+
+    /// Removes consecutive elements for which `same_bucket(current, previous)` returns true.
+    /// The removed slots are set back to the default value.
+    pub fn dedup_by<F>(&mut self, mut same_bucket: F)
+    where F: FnMut(&mut T, &mut T) -> bool
+    {
+        if self.size < 2{
+            return
+        }
+        self.record_slots(0..self.size);
+        let slots = self.raw_slots();
+        let mut kept = 1;
+        for read in 1..self.size{
+            let duplicate = unsafe {same_bucket(&mut *slots.at(read), &mut *slots.at(kept - 1))};
+            if !duplicate{
+                if read != kept{
+                    unsafe {core::ptr::swap(slots.at(read), slots.at(kept))};
+                }
+                kept += 1;
+            }
+        }
+        self.truncate(kept);
+    }
+}
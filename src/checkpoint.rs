@@ -0,0 +1,287 @@
+use crate::SmartBuffer;
+#[cfg(feature = "checkpoint")]
+use crate::storage::Global;
+use crate::storage::Storage;
+
+#[cfg(feature = "checkpoint")]
+use alloc::vec::Vec;
+#[cfg(not(feature = "checkpoint"))]
+use core::marker::PhantomData;
+#[cfg(feature = "checkpoint")]
+use core::mem;
+#[cfg(feature = "checkpoint")]
+use core::ops::{Deref, DerefMut};
+use core::ops::Range;
+
+/// One step of the undo log, replayed in reverse by a rollback.
+#[cfg_attr(not(feature = "checkpoint"), allow(dead_code))]
+pub(crate) enum Undo<T> {
+    /// The size before it changed.
+    Size(usize),
+    /// The value a slot held before it was overwritten.
+    Slot(usize, T),
+    /// A value removed at an index, with everything after it shifted left.
+    Removed(usize, T),
+}
+
+/// The undo log of the open checkpoints of a SmartBuffer.
+#[cfg(feature = "checkpoint")]
+pub(crate) struct Journal<T> {
+    entries: Vec<Undo<T>>,
+    open: usize,
+}
+
+/// Without the `checkpoint` feature nothing is recorded, and the journal takes no room.
+#[cfg(not(feature = "checkpoint"))]
+pub(crate) struct Journal<T>(PhantomData<T>);
+
+impl<T> Journal<T> {
+    #[cfg(feature = "checkpoint")]
+    pub(crate) const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            open: 0,
+        }
+    }
+
+    #[cfg(not(feature = "checkpoint"))]
+    pub(crate) const fn new() -> Self {
+        Self(PhantomData)
+    }
+
+    /// Returns true if a checkpoint is open, so that values only cloned for the undo log can be skipped.
+    #[cfg(feature = "checkpoint")]
+    pub(crate) fn is_recording(&self) -> bool {
+        self.open > 0
+    }
+
+    #[cfg(not(feature = "checkpoint"))]
+    pub(crate) fn is_recording(&self) -> bool {
+        false
+    }
+
+    /// Adds a step to the undo log if a checkpoint is open, otherwise drops it.
+    pub(crate) fn record(&mut self, undo: Undo<T>) {
+        #[cfg(feature = "checkpoint")]
+        if self.open > 0 {
+            self.entries.push(undo);
+        }
+        #[cfg(not(feature = "checkpoint"))]
+        drop(undo);
+    }
+}
+
+impl<T, const N:usize, S> SmartBuffer<T,N,S>
+    where T: Clone,
+        S: Storage<T>
+{
+    /// Records the values of the slots in `range` if a checkpoint is open, for methods that move
+    /// elements around in ways the other steps cannot describe.
+    pub(crate) fn record_slots(&mut self, range: Range<usize>){
+        if self.journal.is_recording(){
+            for i in range{
+                let old = unsafe {self.get_unchecked(i)}.clone();
+                self.journal.record(Undo::Slot(i, old));
+            }
+        }
+    }
+}
+
+/// A point a SmartBuffer can be rolled back to, see [`SmartBuffer::checkpoint`].
+#[cfg(feature = "checkpoint")]
+#[must_use = "a checkpoint stays open until it is rolled back or committed"]
+#[derive(Debug)]
+pub struct Checkpoint {
+    entries: usize,
+    size: usize,
+}
+
+#[cfg(feature = "checkpoint")]
+impl<T, const N:usize, S> SmartBuffer<T,N,S>
+    where T: Clone,
+        S: Storage<T>
+{
+    /// Starts recording changes so they can be undone with [`rollback`](Self::rollback).
+    ///
+    /// Changes made by `push`, `insert`, `remove`, `truncate`, `set_size`, `clear`, `fill`, `map`,
+    /// the sorting, `reverse`, `dedup` and shifting methods, and the methods built on them are
+    /// recorded, including the values they overwrite or move. Writes through indexing, `iter_mut`,
+    /// `as_mut_slices` or raw slots are not. Checkpoints nest, and each one must be rolled back
+    /// or committed before the ones taken earlier.
+    ///
+    /// Only available with the `checkpoint` feature, which gives every SmartBuffer an undo log.
+    ///
+    /// ```
+    /// use smart_buffer::SmartBuffer;
+    ///
+    /// let mut tokens = SmartBuffer::<char, 4>::new(' ', 4);
+    /// tokens.push('a');
+    /// let checkpoint = tokens.checkpoint();
+    /// tokens.insert('x', 0);
+    /// tokens.push('b');
+    /// tokens.rollback(checkpoint);
+    /// assert_eq!(tokens.len(), 1);
+    /// assert_eq!(tokens[0], 'a');
+    /// ```
+    pub fn checkpoint(&mut self) -> Checkpoint{
+        self.journal.open += 1;
+        Checkpoint{
+            entries: self.journal.entries.len(),
+            size: self.size,
+        }
+    }
+
+    /// Undoes every recorded change made since `checkpoint` was taken and closes it.
+    ///
+    /// Panics if `checkpoint` does not belong to this SmartBuffer, or if an earlier checkpoint
+    /// was already closed.
+    pub fn rollback(&mut self, checkpoint: Checkpoint){
+        assert!(self.journal.open > 0 && checkpoint.entries <= self.journal.entries.len(), "the checkpoint is no longer open");
+        // replay with recording switched off
+        let mut entries = mem::take(&mut self.journal.entries);
+        let open = mem::replace(&mut self.journal.open, 0);
+        for undo in entries.drain(checkpoint.entries..).rev(){
+            match undo{
+                Undo::Size(size) => self.restore_size(size),
+                Undo::Slot(index, value) => {
+                    self.ensure_slots(index + 1);
+                    unsafe {*self.get_mut_unchecked(index) = value};
+                }
+                Undo::Removed(index, value) => {
+                    self.ensure_slots(self.size + 1);
+                    self.size += 1;
+                    for i in ((index + 1)..self.size).rev(){
+                        self.swap_slots(i, i - 1);
+                    }
+                    unsafe {*self.get_mut_unchecked(index) = value};
+                }
+            }
+        }
+        self.restore_size(checkpoint.size);
+        self.journal = Journal{entries, open};
+        self.close_checkpoint();
+    }
+
+    /// Keeps the changes made since `checkpoint` was taken and closes it. They can still be
+    /// undone by rolling back an earlier checkpoint.
+    pub fn commit(&mut self, checkpoint: Checkpoint){
+        assert!(self.journal.open > 0 && checkpoint.entries <= self.journal.entries.len(), "the checkpoint is no longer open");
+        self.close_checkpoint();
+    }
+
+    /// Takes a checkpoint that is rolled back when the returned guard is dropped, unless it is
+    /// committed first.
+    ///
+    /// ```
+    /// use smart_buffer::SmartBuffer;
+    ///
+    /// let mut tokens = SmartBuffer::<u8, 8>::new(0, 8);
+    /// {
+    ///     let mut speculative = tokens.transaction();
+    ///     speculative.insert_slice(b"if");
+    ///     // not committed
+    /// }
+    /// assert!(tokens.is_empty());
+    ///
+    /// let mut speculative = tokens.transaction();
+    /// speculative.insert_slice(b"else");
+    /// speculative.commit();
+    /// assert_eq!(tokens.len(), 4);
+    /// ```
+    pub fn transaction(&mut self) -> Transaction<'_,T,N,S>{
+        let checkpoint = self.checkpoint();
+        Transaction{
+            buf: self,
+            checkpoint: Some(checkpoint),
+        }
+    }
+
+    fn close_checkpoint(&mut self){
+        self.journal.open -= 1;
+        if self.journal.open == 0{
+            self.journal.entries.clear();
+        }
+    }
+
+    fn restore_size(&mut self, size:usize){
+        if size < self.size{
+            self.truncate(size);
+        } else {
+            self.ensure_slots(size);
+            self.size = size;
+        }
+    }
+
+    /// Grows the capacity to at least `slots`, the heap may have been given back since the
+    /// change was recorded.
+    fn ensure_slots(&mut self, slots:usize){
+        if slots > self.capacity{
+            self.reserve(slots - self.size);
+        }
+    }
+}
+
+/// Rolls back to a checkpoint when dropped, unless committed. Derefs to the SmartBuffer, see
+/// [`SmartBuffer::transaction`].
+#[cfg(feature = "checkpoint")]
+pub struct Transaction<'a, T, const N:usize, S = Global>
+    where T: Clone,
+        S: Storage<T>
+{
+    buf: &'a mut SmartBuffer<T,N,S>,
+    checkpoint: Option<Checkpoint>,
+}
+
+#[cfg(feature = "checkpoint")]
+impl<T, const N:usize, S> Transaction<'_,T,N,S>
+    where T: Clone,
+        S: Storage<T>
+{
+    /// Keeps the changes.
+    pub fn commit(mut self){
+        if let Some(checkpoint) = self.checkpoint.take(){
+            self.buf.commit(checkpoint);
+        }
+    }
+
+    /// Undoes the changes now rather than when the guard is dropped.
+    pub fn rollback(mut self){
+        if let Some(checkpoint) = self.checkpoint.take(){
+            self.buf.rollback(checkpoint);
+        }
+    }
+}
+
+#[cfg(feature = "checkpoint")]
+impl<T, const N:usize, S> Deref for Transaction<'_,T,N,S>
+    where T: Clone,
+        S: Storage<T>
+{
+    type Target = SmartBuffer<T,N,S>;
+
+    fn deref(&self) -> &Self::Target {
+        self.buf
+    }
+}
+
+#[cfg(feature = "checkpoint")]
+impl<T, const N:usize, S> DerefMut for Transaction<'_,T,N,S>
+    where T: Clone,
+        S: Storage<T>
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.buf
+    }
+}
+
+#[cfg(feature = "checkpoint")]
+impl<T, const N:usize, S> Drop for Transaction<'_,T,N,S>
+    where T: Clone,
+        S: Storage<T>
+{
+    fn drop(&mut self) {
+        if let Some(checkpoint) = self.checkpoint.take(){
+            self.buf.rollback(checkpoint);
+        }
+    }
+}
@@ -0,0 +1,427 @@
+use crate::SmartBuffer;
+use crate::storage::Storage;
+
+use core::fmt::{Debug, Formatter};
+use core::iter::{Chain, FusedIterator};
+use core::ops::Index;
+use core::slice;
+
+impl<T, const N:usize, S> SmartBuffer<T,N,S>
+    where T: Clone,
+        S: Storage<T>
+{
+    /// Returns an iterator over `chunk_size` elements at a time, starting at the front. The last
+    /// chunk is shorter if `chunk_size` does not divide the length.
+    ///
+    /// Chunks are borrowed in place. A chunk that crosses from the stack to the heap is a
+    /// [`Chunk::Split`] of two slices instead of one.
+    ///
+    /// Panics if `chunk_size` is 0.
+    ///
+    /// ```
+    /// use smart_buffer::SmartBuffer;
+    ///
+    /// # #[cfg(feature = "alloc")] {
+    /// let mut samples = SmartBuffer::<i16, 4>::new(0, 6);
+    /// samples.insert_slice(&[1, 2, 3, 4, 5, 6]);
+    /// let mut chunks = samples.chunks(3);
+    /// assert_eq!(chunks.next().unwrap().as_slice(), Some(&[1, 2, 3][..]));
+    /// assert_eq!(chunks.next().unwrap().as_slices(), (&[4][..], &[5, 6][..]));
+    /// assert!(chunks.next().is_none());
+    /// # }
+    /// ```
+    pub fn chunks(&self, chunk_size:usize) -> Chunks<'_,T>{
+        assert!(chunk_size != 0, "chunk size must be non-zero");
+        Chunks{
+            view: View::new(self.as_slices()),
+            chunk_size,
+        }
+    }
+
+    /// Like [`chunks`](Self::chunks), but leaves out the last elements if `chunk_size` does not
+    /// divide the length. They are available from [`ChunksExact::remainder`].
+    ///
+    /// Panics if `chunk_size` is 0.
+    pub fn chunks_exact(&self, chunk_size:usize) -> ChunksExact<'_,T>{
+        assert!(chunk_size != 0, "chunk size must be non-zero");
+        let mut view = View::new(self.as_slices());
+        let rem = view.len() % chunk_size;
+        let remainder = view.chunk(view.end - rem, view.end);
+        view.end -= rem;
+        ChunksExact{
+            view,
+            chunk_size,
+            remainder,
+        }
+    }
+
+    /// Like [`chunks`](Self::chunks), but starting at the back. The last chunk is the shorter one.
+    ///
+    /// Panics if `chunk_size` is 0.
+    pub fn rchunks(&self, chunk_size:usize) -> RChunks<'_,T>{
+        assert!(chunk_size != 0, "chunk size must be non-zero");
+        RChunks{
+            view: View::new(self.as_slices()),
+            chunk_size,
+        }
+    }
+
+    /// Returns an iterator over every run of `size` consecutive elements, overlapping. Yields
+    /// nothing if the buffer is shorter than `size`.
+    ///
+    /// Panics if `size` is 0.
+    ///
+    /// ```
+    /// use smart_buffer::SmartBuffer;
+    ///
+    /// # #[cfg(feature = "alloc")] {
+    /// let mut samples = SmartBuffer::<f32, 4>::new(0.0, 6);
+    /// samples.insert_slice(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+    /// let averages = samples.windows(3)
+    ///     .map(|w| w.to_array::<3>().unwrap().iter().sum::<f32>() / 3.0)
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(averages, [2.0, 3.0, 4.0, 5.0]);
+    /// # }
+    /// ```
+    pub fn windows(&self, size:usize) -> Windows<'_,T>{
+        assert!(size != 0, "window size must be non-zero");
+        Windows{
+            view: View::new(self.as_slices()),
+            size,
+        }
+    }
+}
+
+/// A borrowed run of consecutive elements of a SmartBuffer, yielded by [`SmartBuffer::chunks`]
+/// and friends.
+pub enum Chunk<'a, T>{
+    /// The elements lie in one region, the stack or the heap.
+    Contiguous(&'a [T]),
+    /// The elements cross from the stack, the first slice, to the heap, the second one.
+    Split(&'a [T], &'a [T]),
+}
+
+impl<'a, T> Chunk<'a,T>{
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize{
+        let (first, second) = self.as_slices();
+        first.len() + second.len()
+    }
+
+    /// Returns true if the chunk holds no elements.
+    pub fn is_empty(&self) -> bool{
+        self.len() == 0
+    }
+
+    /// Returns the elements as two slices. The second one is empty unless the chunk is split.
+    pub fn as_slices(&self) -> (&'a [T], &'a [T]){
+        match *self{
+            Chunk::Contiguous(elems) => (elems, &[]),
+            Chunk::Split(first, second) => (first, second),
+        }
+    }
+
+    /// Returns the elements as one slice, or `None` if the chunk is split.
+    pub fn as_slice(&self) -> Option<&'a [T]>{
+        match *self{
+            Chunk::Contiguous(elems) => Some(elems),
+            Chunk::Split(..) => None,
+        }
+    }
+
+    /// Safely get a value at an index
+    pub fn get(&self, index:usize) -> Option<&'a T>{
+        let (first, second) = self.as_slices();
+        match index.checked_sub(first.len()){
+            None => first.get(index),
+            Some(index) => second.get(index),
+        }
+    }
+
+    /// Returns an iterator over the elements.
+    pub fn iter(&self) -> Chain<slice::Iter<'a,T>, slice::Iter<'a,T>>{
+        let (first, second) = self.as_slices();
+        first.iter().chain(second)
+    }
+
+    /// Copies the elements into `dst`. Panics if the lengths differ.
+    pub fn copy_to_slice(&self, dst: &mut [T])
+    where T: Copy
+    {
+        assert_eq!(dst.len(), self.len(), "destination and chunk lengths differ");
+        let (first, second) = self.as_slices();
+        dst[..first.len()].copy_from_slice(first);
+        dst[first.len()..].copy_from_slice(second);
+    }
+
+    /// Copies the elements into an array on the stack, or returns `None` if the chunk does not
+    /// hold exactly `K` elements.
+    pub fn to_array<const K:usize>(&self) -> Option<[T; K]>
+    where T: Copy
+    {
+        let (first, second) = self.as_slices();
+        if self.len() != K{
+            return None
+        }
+        Some(core::array::from_fn(|i| match i.checked_sub(first.len()){
+            None => first[i],
+            Some(i) => second[i],
+        }))
+    }
+}
+
+impl<T> Clone for Chunk<'_,T>{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Chunk<'_,T> {}
+
+impl<T> Index<usize> for Chunk<'_,T>{
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        match self.get(index){
+            Some(elem) => elem,
+            None => panic!("index {} is out of bounds for a chunk of length {}", index, self.len()),
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for Chunk<'a,T>{
+    type Item = &'a T;
+    type IntoIter = Chain<slice::Iter<'a,T>, slice::Iter<'a,T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: PartialEq> PartialEq<[T]> for Chunk<'_,T>{
+    fn eq(&self, other: &[T]) -> bool {
+        self.len() == other.len() && self.iter().eq(other)
+    }
+}
+
+impl<T: Debug> Debug for Chunk<'_,T>{
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_list()
+            .entries(self.iter())
+            .finish()
+    }
+}
+
+/// The elements left to iterate over, `start..end`, across both regions.
+#[derive(Clone)]
+struct View<'a, T>{
+    stack: &'a [T],
+    heap: &'a [T],
+    start: usize,
+    end: usize,
+}
+
+impl<'a, T> View<'a,T>{
+    fn new((stack, heap): (&'a [T], &'a [T])) -> Self{
+        Self{
+            stack,
+            heap,
+            start: 0,
+            end: stack.len() + heap.len(),
+        }
+    }
+
+    fn len(&self) -> usize{
+        self.end - self.start
+    }
+
+    /// Borrows `start..end`, as one slice if it lies in one region.
+    fn chunk(&self, start:usize, end:usize) -> Chunk<'a,T>{
+        let inline = self.stack.len();
+        if end <= inline{
+            Chunk::Contiguous(&self.stack[start..end])
+        } else if start >= inline{
+            Chunk::Contiguous(&self.heap[start - inline..end - inline])
+        } else {
+            Chunk::Split(&self.stack[start..], &self.heap[..end - inline])
+        }
+    }
+}
+
+/// Iterator over chunks of a SmartBuffer, see [`SmartBuffer::chunks`].
+#[derive(Clone)]
+pub struct Chunks<'a, T>{
+    view: View<'a,T>,
+    chunk_size: usize,
+}
+
+impl<'a, T> Iterator for Chunks<'a,T>{
+    type Item = Chunk<'a,T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.view.start == self.view.end{
+            return None
+        }
+        let end = self.view.end.min(self.view.start + self.chunk_size);
+        let chunk = self.view.chunk(self.view.start, end);
+        self.view.start = end;
+        Some(chunk)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.view.len().div_ceil(self.chunk_size);
+        (len, Some(len))
+    }
+}
+
+impl<T> DoubleEndedIterator for Chunks<'_,T>{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.view.start == self.view.end{
+            return None
+        }
+        let last = match self.view.len() % self.chunk_size{
+            0 => self.chunk_size,
+            rem => rem,
+        };
+        let chunk = self.view.chunk(self.view.end - last, self.view.end);
+        self.view.end -= last;
+        Some(chunk)
+    }
+}
+
+impl<T> ExactSizeIterator for Chunks<'_,T> {}
+
+impl<T> FusedIterator for Chunks<'_,T> {}
+
+/// Iterator over chunks of exactly the same length, see [`SmartBuffer::chunks_exact`].
+#[derive(Clone)]
+pub struct ChunksExact<'a, T>{
+    view: View<'a,T>,
+    chunk_size: usize,
+    remainder: Chunk<'a,T>,
+}
+
+impl<'a, T> ChunksExact<'a,T>{
+    /// Returns the last elements that do not make up a whole chunk.
+    pub fn remainder(&self) -> Chunk<'a,T>{
+        self.remainder
+    }
+}
+
+impl<'a, T> Iterator for ChunksExact<'a,T>{
+    type Item = Chunk<'a,T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.view.start == self.view.end{
+            return None
+        }
+        let chunk = self.view.chunk(self.view.start, self.view.start + self.chunk_size);
+        self.view.start += self.chunk_size;
+        Some(chunk)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.view.len() / self.chunk_size;
+        (len, Some(len))
+    }
+}
+
+impl<T> DoubleEndedIterator for ChunksExact<'_,T>{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.view.start == self.view.end{
+            return None
+        }
+        let chunk = self.view.chunk(self.view.end - self.chunk_size, self.view.end);
+        self.view.end -= self.chunk_size;
+        Some(chunk)
+    }
+}
+
+impl<T> ExactSizeIterator for ChunksExact<'_,T> {}
+
+impl<T> FusedIterator for ChunksExact<'_,T> {}
+
+/// Iterator over chunks of a SmartBuffer starting at the back, see [`SmartBuffer::rchunks`].
+#[derive(Clone)]
+pub struct RChunks<'a, T>{
+    view: View<'a,T>,
+    chunk_size: usize,
+}
+
+impl<'a, T> Iterator for RChunks<'a,T>{
+    type Item = Chunk<'a,T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.view.start == self.view.end{
+            return None
+        }
+        let start = self.view.end.saturating_sub(self.chunk_size).max(self.view.start);
+        let chunk = self.view.chunk(start, self.view.end);
+        self.view.end = start;
+        Some(chunk)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.view.len().div_ceil(self.chunk_size);
+        (len, Some(len))
+    }
+}
+
+impl<T> DoubleEndedIterator for RChunks<'_,T>{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.view.start == self.view.end{
+            return None
+        }
+        let first = match self.view.len() % self.chunk_size{
+            0 => self.chunk_size,
+            rem => rem,
+        };
+        let chunk = self.view.chunk(self.view.start, self.view.start + first);
+        self.view.start += first;
+        Some(chunk)
+    }
+}
+
+impl<T> ExactSizeIterator for RChunks<'_,T> {}
+
+impl<T> FusedIterator for RChunks<'_,T> {}
+
+/// Iterator over overlapping windows of a SmartBuffer, see [`SmartBuffer::windows`].
+#[derive(Clone)]
+pub struct Windows<'a, T>{
+    view: View<'a,T>,
+    size: usize,
+}
+
+impl<'a, T> Iterator for Windows<'a,T>{
+    type Item = Chunk<'a,T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.view.len() < self.size{
+            return None
+        }
+        let chunk = self.view.chunk(self.view.start, self.view.start + self.size);
+        self.view.start += 1;
+        Some(chunk)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.view.len() + 1).saturating_sub(self.size);
+        (len, Some(len))
+    }
+}
+
+impl<T> DoubleEndedIterator for Windows<'_,T>{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.view.len() < self.size{
+            return None
+        }
+        let chunk = self.view.chunk(self.view.end - self.size, self.view.end);
+        self.view.end -= 1;
+        Some(chunk)
+    }
+}
+
+impl<T> ExactSizeIterator for Windows<'_,T> {}
+
+impl<T> FusedIterator for Windows<'_,T> {}
@@ -0,0 +1,297 @@
+use crate::SmartBuffer;
+use crate::storage::Storage;
+use crate::error::{ReadError, TryReserveError};
+
+/// Generates the `put_*` and `get_*` methods of the fixed-width number types.
+macro_rules! fixed_width {
+    ($($ty:ident: $put_le:ident, $put_be:ident, $try_put_le:ident, $try_put_be:ident, $get_le:ident, $get_be:ident;)*) => {$(
+        #[doc = concat!("Appends a `", stringify!($ty), "` in little-endian byte order.")]
+        pub fn $put_le(&mut self, value: $ty){
+            self.put_slice(&value.to_le_bytes());
+        }
+
+        #[doc = concat!("Appends a `", stringify!($ty), "` in big-endian byte order.")]
+        pub fn $put_be(&mut self, value: $ty){
+            self.put_slice(&value.to_be_bytes());
+        }
+
+        #[doc = concat!("Fallible version of [`", stringify!($put_le), "`](Self::", stringify!($put_le), ").")]
+        pub fn $try_put_le(&mut self, value: $ty) -> Result<(), TryReserveError>{
+            self.try_put_slice(&value.to_le_bytes())
+        }
+
+        #[doc = concat!("Fallible version of [`", stringify!($put_be), "`](Self::", stringify!($put_be), ").")]
+        pub fn $try_put_be(&mut self, value: $ty) -> Result<(), TryReserveError>{
+            self.try_put_slice(&value.to_be_bytes())
+        }
+
+        #[doc = concat!("Reads a little-endian `", stringify!($ty), "` at the cursor.")]
+        pub fn $get_le(&mut self) -> Result<$ty, ReadError>{
+            self.read_array().map($ty::from_le_bytes)
+        }
+
+        #[doc = concat!("Reads a big-endian `", stringify!($ty), "` at the cursor.")]
+        pub fn $get_be(&mut self) -> Result<$ty, ReadError>{
+            self.read_array().map($ty::from_be_bytes)
+        }
+    )*};
+}
+
+/// Binary encoding and decoding. The `put_*` methods append at the size and grow the SmartBuffer
+/// like [`push`](SmartBuffer::push) after reserving room, the `get_*` methods read from the
+/// cursor up to the size and move the cursor past what they read. Values may straddle the
+/// stack and the heap.
+///
+/// The `put_*` methods panic like [`reserve`](SmartBuffer::reserve) when the storage has no room,
+/// which without the `alloc` feature means as soon as the stack is full. Each has a `try_put_*`
+/// version that returns the error instead and appends nothing.
+impl<const N:usize, S> SmartBuffer<u8,N,S>
+    where S: Storage<u8>
+{
+    /// Appends a slice of bytes, reserving room for all of them first.
+    ///
+    /// ```
+    /// use smart_buffer::SmartBuffer;
+    ///
+    /// # #[cfg(feature = "alloc")] {
+    /// let mut packet = SmartBuffer::<u8, 4>::new(0, 4);
+    /// packet.put_slice(b"HDR");
+    /// packet.put_u32_be(0xdead_beef); // crosses onto the heap
+    /// packet.put_uvarint(300);
+    ///
+    /// let mut magic = [0; 3];
+    /// packet.copy_to_slice(&mut magic).unwrap();
+    /// assert_eq!(&magic, b"HDR");
+    /// assert_eq!(packet.get_u32_be(), Ok(0xdead_beef));
+    /// assert_eq!(packet.get_uvarint(), Ok(300));
+    /// assert_eq!(packet.remaining(), 0);
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the storage has no room for the bytes, see [`try_put_slice`](Self::try_put_slice).
+    pub fn put_slice(&mut self, bytes: &[u8]){
+        self.reserve(bytes.len());
+        self.insert_slice(bytes);
+    }
+
+    /// Appends a slice of bytes, or returns the error and appends nothing if the storage has no
+    /// room for all of them.
+    ///
+    /// ```
+    /// use smart_buffer::SmartBuffer;
+    /// use core::mem::MaybeUninit;
+    ///
+    /// let mut region = [MaybeUninit::uninit(); 4];
+    /// let mut frame = SmartBuffer::<u8, 4, _>::with_overflow(0, &mut region);
+    /// assert!(frame.try_put_u32_be(0xdead_beef).is_ok());
+    /// assert!(frame.try_put_u32_le(1).is_ok()); // into the overflow region
+    /// assert!(frame.try_put_u8(0).is_err());
+    /// assert_eq!(frame.len(), 8);
+    /// ```
+    pub fn try_put_slice(&mut self, bytes: &[u8]) -> Result<(), TryReserveError>{
+        self.try_reserve(bytes.len())?;
+        self.insert_slice(bytes);
+        Ok(())
+    }
+
+    /// Appends a `u8`.
+    pub fn put_u8(&mut self, value: u8){
+        self.put_slice(&[value]);
+    }
+
+    /// Appends an `i8`.
+    pub fn put_i8(&mut self, value: i8){
+        self.put_slice(&value.to_le_bytes());
+    }
+
+    /// Fallible version of [`put_u8`](Self::put_u8).
+    pub fn try_put_u8(&mut self, value: u8) -> Result<(), TryReserveError>{
+        self.try_put_slice(&[value])
+    }
+
+    /// Fallible version of [`put_i8`](Self::put_i8).
+    pub fn try_put_i8(&mut self, value: i8) -> Result<(), TryReserveError>{
+        self.try_put_slice(&value.to_le_bytes())
+    }
+
+    fixed_width! {
+        u16: put_u16_le, put_u16_be, try_put_u16_le, try_put_u16_be, get_u16_le, get_u16_be;
+        u32: put_u32_le, put_u32_be, try_put_u32_le, try_put_u32_be, get_u32_le, get_u32_be;
+        u64: put_u64_le, put_u64_be, try_put_u64_le, try_put_u64_be, get_u64_le, get_u64_be;
+        u128: put_u128_le, put_u128_be, try_put_u128_le, try_put_u128_be, get_u128_le, get_u128_be;
+        i16: put_i16_le, put_i16_be, try_put_i16_le, try_put_i16_be, get_i16_le, get_i16_be;
+        i32: put_i32_le, put_i32_be, try_put_i32_le, try_put_i32_be, get_i32_le, get_i32_be;
+        i64: put_i64_le, put_i64_be, try_put_i64_le, try_put_i64_be, get_i64_le, get_i64_be;
+        i128: put_i128_le, put_i128_be, try_put_i128_le, try_put_i128_be, get_i128_le, get_i128_be;
+        f32: put_f32_le, put_f32_be, try_put_f32_le, try_put_f32_be, get_f32_le, get_f32_be;
+        f64: put_f64_le, put_f64_be, try_put_f64_le, try_put_f64_be, get_f64_le, get_f64_be;
+    }
+
+    /// Appends an unsigned LEB128 varint, which takes 1 to 10 bytes.
+    pub fn put_uvarint(&mut self, value: u64){
+        let (bytes, len) = encode_uvarint(value);
+        self.put_slice(&bytes[..len]);
+    }
+
+    /// Fallible version of [`put_uvarint`](Self::put_uvarint).
+    pub fn try_put_uvarint(&mut self, value: u64) -> Result<(), TryReserveError>{
+        let (bytes, len) = encode_uvarint(value);
+        self.try_put_slice(&bytes[..len])
+    }
+
+    /// Appends a signed LEB128 varint, which takes 1 to 10 bytes.
+    pub fn put_ivarint(&mut self, value: i64){
+        let (bytes, len) = encode_ivarint(value);
+        self.put_slice(&bytes[..len]);
+    }
+
+    /// Fallible version of [`put_ivarint`](Self::put_ivarint).
+    pub fn try_put_ivarint(&mut self, value: i64) -> Result<(), TryReserveError>{
+        let (bytes, len) = encode_ivarint(value);
+        self.try_put_slice(&bytes[..len])
+    }
+
+    /// Returns the position the `get_*` methods read from next. It moves back to the size when
+    /// the SmartBuffer shrinks below it.
+    pub fn cursor(&self) -> usize{
+        self.cursor
+    }
+
+    /// Moves the read cursor to `pos`. Panics if `pos` is past the size.
+    pub fn set_cursor(&mut self, pos:usize){
+        assert!(pos <= self.size, "cursor {} is past the size {}", pos, self.size);
+        self.cursor = pos;
+    }
+
+    /// Returns the number of bytes between the cursor and the size.
+    pub fn remaining(&self) -> usize{
+        self.size.saturating_sub(self.cursor)
+    }
+
+    /// Fills `out` with the bytes at the cursor.
+    pub fn copy_to_slice(&mut self, out: &mut [u8]) -> Result<(), ReadError>{
+        let available = self.remaining();
+        if out.len() > available{
+            return Err(ReadError::UnexpectedEnd {needed: out.len(), available})
+        }
+        let (stack, heap) = self.as_slices();
+        let (first, second) = if self.cursor < stack.len(){
+            (&stack[self.cursor..], heap)
+        } else {
+            (&[][..], &heap[self.cursor - stack.len()..])
+        };
+        let split = first.len().min(out.len());
+        out[..split].copy_from_slice(&first[..split]);
+        let rest = out.len() - split;
+        out[split..].copy_from_slice(&second[..rest]);
+        self.cursor += out.len();
+        Ok(())
+    }
+
+    /// Reads a `u8` at the cursor.
+    pub fn get_u8(&mut self) -> Result<u8, ReadError>{
+        self.read_array().map(u8::from_le_bytes)
+    }
+
+    /// Reads an `i8` at the cursor.
+    pub fn get_i8(&mut self) -> Result<i8, ReadError>{
+        self.read_array().map(i8::from_le_bytes)
+    }
+
+    /// Reads an unsigned LEB128 varint at the cursor.
+    pub fn get_uvarint(&mut self) -> Result<u64, ReadError>{
+        let mut pos = self.cursor;
+        let mut result = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte_at(pos)?;
+            pos += 1;
+            // the tenth byte only has room for the top bit
+            if shift == 63 && byte > 1{
+                return Err(ReadError::VarintOverflow)
+            }
+            result |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0{
+                self.cursor = pos;
+                return Ok(result)
+            }
+            shift += 7;
+        }
+    }
+
+    /// Reads a signed LEB128 varint at the cursor.
+    pub fn get_ivarint(&mut self) -> Result<i64, ReadError>{
+        let mut pos = self.cursor;
+        let mut result = 0i64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte_at(pos)?;
+            pos += 1;
+            // the tenth byte only has room for the sign bit
+            if shift == 63 && byte != 0 && byte != 0x7f{
+                return Err(ReadError::VarintOverflow)
+            }
+            result |= i64::from(byte & 0x7f) << shift;
+            shift += 7;
+            if byte & 0x80 == 0{
+                if shift < 64 && byte & 0x40 != 0{
+                    result |= -1 << shift;
+                }
+                self.cursor = pos;
+                return Ok(result)
+            }
+        }
+    }
+
+    fn read_array<const M:usize>(&mut self) -> Result<[u8; M], ReadError>{
+        let mut bytes = [0; M];
+        self.copy_to_slice(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Returns the byte at `pos` for a varint that started at the cursor.
+    fn byte_at(&self, pos:usize) -> Result<u8, ReadError>{
+        if pos >= self.size{
+            return Err(ReadError::UnexpectedEnd {needed: pos + 1 - self.cursor, available: self.remaining()})
+        }
+        Ok(unsafe {*self.get_unchecked(pos)})
+    }
+}
+
+/// Encodes an unsigned LEB128 varint, returning the bytes and how many of them are used.
+fn encode_uvarint(mut value: u64) -> ([u8; 10], usize){
+    let mut bytes = [0; 10];
+    let mut len = 0;
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0{
+            bytes[len] = byte;
+            len += 1;
+            break
+        }
+        bytes[len] = byte | 0x80;
+        len += 1;
+    }
+    (bytes, len)
+}
+
+/// Encodes a signed LEB128 varint, returning the bytes and how many of them are used.
+fn encode_ivarint(mut value: i64) -> ([u8; 10], usize){
+    let mut bytes = [0; 10];
+    let mut len = 0;
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        // done once the rest is only copies of the sign bit of this byte
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0){
+            bytes[len] = byte;
+            len += 1;
+            break
+        }
+        bytes[len] = byte | 0x80;
+        len += 1;
+    }
+    (bytes, len)
+}
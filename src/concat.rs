@@ -0,0 +1,158 @@
+use crate::SmartBuffer;
+use crate::checkpoint::Undo;
+use crate::error::TryReserveError;
+use crate::storage::Storage;
+
+use core::mem;
+use core::ops::{Bound, RangeBounds};
+
+impl<T, const N:usize, S> SmartBuffer<T,N,S>
+    where T: Clone,
+        S: Storage<T>
+{
+    /// Moves every element of `other` to the end of this buffer, leaving `other` empty. The room
+    /// is reserved first, so this allocates at most once.
+    ///
+    /// If the storage runs out of room, for example without the `alloc` feature, only the
+    /// elements that fit are moved, and the rest stay at the front of `other`.
+    ///
+    /// ```
+    /// use smart_buffer::SmartBuffer;
+    ///
+    /// # #[cfg(feature = "alloc")] {
+    /// let mut header = SmartBuffer::<u8, 4>::new(0, 4);
+    /// header.insert_slice(&[0xca, 0xfe]);
+    /// let mut body = SmartBuffer::<u8, 16>::new(0, 16);
+    /// body.insert_slice(&[1, 2, 3]);
+    ///
+    /// header.append(&mut body);
+    /// assert_eq!(header.as_slices(), (&[0xca, 0xfe, 1, 2][..], &[3][..]));
+    /// assert!(body.is_empty());
+    /// # }
+    /// ```
+    pub fn append<const M:usize, P>(&mut self, other: &mut SmartBuffer<T,M,P>)
+    where P: Storage<T>
+    {
+        let count = other.size;
+        self.reserve_what_fits(count);
+        let moved = count.min(self.capacity - self.size);
+        for i in 0..moved{
+            self.push(other.take_slot(i));
+        }
+        other.record_slots(moved..count);
+        for i in moved..count{
+            other.swap_slots(i - moved, i);
+        }
+        other.set_size(count - moved);
+    }
+
+    /// Splits the buffer in two at `at`, returning the elements from `at` on in a new buffer
+    /// with the same default value and spill strategy, on a clone of the storage. This buffer
+    /// keeps its capacity.
+    ///
+    /// Panics if `at` is past the size.
+    pub fn split_off(&mut self, at:usize) -> Self
+    where S: Clone
+    {
+        assert!(at <= self.size, "`at` split index (is {}) should be <= len (is {})", at, self.size);
+        let mut tail = Self::from_default_in(self.default.clone(), self.storage.clone()).with_spill(self.spill);
+        let count = self.size - at;
+        if count > N{
+            tail.reserve_exact(count);
+        }
+        for i in at..self.size{
+            tail.push(self.take_slot(i));
+        }
+        self.set_size(at);
+        tail
+    }
+
+    /// Clones the elements in `range` to the end of the buffer, allocating at most once.
+    /// If the storage runs out of room, only the clones that fit are appended.
+    ///
+    /// Panics if the range is out of bounds.
+    ///
+    /// ```
+    /// use smart_buffer::SmartBuffer;
+    ///
+    /// let mut pattern = SmartBuffer::<char, 8>::new(' ', 8);
+    /// pattern.insert_slice(&['a', 'b', 'c']);
+    /// pattern.extend_from_within(1..);
+    /// assert_eq!(pattern.iter().collect::<String>(), "abcbc");
+    /// ```
+    pub fn extend_from_within<R>(&mut self, range: R)
+    where R: RangeBounds<usize>
+    {
+        let start = match range.start_bound(){
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.checked_add(1).expect("range start overflows"),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound(){
+            Bound::Included(&end) => end.checked_add(1).expect("range end overflows"),
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.size,
+        };
+        assert!(start <= end && end <= self.size, "range {}..{} is out of bounds for a SmartBuffer of length {}", start, end, self.size);
+        self.reserve_what_fits(end - start);
+        for i in start..end.min(start + self.capacity - self.size){
+            let elem = unsafe {self.get_unchecked(i)}.clone();
+            self.push(elem);
+        }
+    }
+
+    /// Moves the element at `index` out, leaving the default value in its slot.
+    fn take_slot(&mut self, index:usize) -> T{
+        let default = self.default.clone();
+        let value = mem::replace(unsafe {self.get_mut_unchecked(index)}, default);
+        if self.journal.is_recording(){
+            self.journal.record(Undo::Slot(index, value.clone()));
+        }
+        value
+    }
+}
+
+/// Concatenation onto the global allocator.
+impl<T, const N:usize> SmartBuffer<T,N>
+    where T: Clone
+{
+    /// Moves the elements of every buffer in `parts` into one new buffer with `default` as its
+    /// default value, leaving them empty. Allocates at most once.
+    ///
+    /// Returns [`TryReserveError::CapacityOverflow`] if the combined length overflows `usize` or
+    /// does not fit, which without the `alloc` feature is any length past `N`, and
+    /// [`TryReserveError::AllocError`] if the allocation fails. The parts are untouched then.
+    ///
+    /// ```
+    /// use smart_buffer::SmartBuffer;
+    ///
+    /// # #[cfg(feature = "alloc")] {
+    /// let mut parts = [SmartBuffer::<u16, 2>::from_fn(2, |i| i as u16), SmartBuffer::from_fn(3, |i| 10 + i as u16)];
+    /// let message = SmartBuffer::<u16, 8>::concat(&mut parts, 0).unwrap();
+    /// assert_eq!(message.iter().copied().collect::<Vec<_>>(), [0, 1, 10, 11, 12]);
+    /// assert!(parts.iter().all(SmartBuffer::is_empty));
+    /// # }
+    /// ```
+    pub fn concat<const M:usize, S>(parts: &mut [SmartBuffer<T,M,S>], default: T) -> Result<Self, TryReserveError>
+    where S: Storage<T>
+    {
+        Self::join(parts, &[], default)
+    }
+
+    /// Like [`concat`](Self::concat), with a clone of `separator` between the parts.
+    pub fn join<const M:usize, S>(parts: &mut [SmartBuffer<T,M,S>], separator: &[T], default: T) -> Result<Self, TryReserveError>
+    where S: Storage<T>
+    {
+        let separators = separator.len().checked_mul(parts.len().saturating_sub(1));
+        let len = separators.and_then(|separators| parts.iter().try_fold(separators, |len, part| len.checked_add(part.size)));
+        let mut out = Self::from_default(default);
+        out.try_reserve_exact(len.ok_or(TryReserveError::CapacityOverflow)?)?;
+        for (i, part) in parts.iter_mut().enumerate(){
+            if i > 0{
+                out.insert_slice(separator);
+            }
+            out.append(part);
+        }
+        Ok(out)
+    }
+}
@@ -0,0 +1,326 @@
+use crate::SmartBuffer;
+
+use alloc::rc::Rc;
+use alloc::sync::Arc;
+use core::fmt::{Debug, Formatter};
+use core::iter::{repeat_with, FromIterator};
+use core::mem;
+use core::ops::{Deref, Index, IndexMut};
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// A reference-counted slice that can hold the heap segment of a [`CowBuffer`], implemented for
+/// `Arc<[T]>` and `Rc<[T]>`.
+pub trait SharedSlots<T>: Deref<Target = [T]> + Clone + FromIterator<T> + private::Sealed {
+    /// Returns the slots, cloning them first if they are shared.
+    fn make_mut(this: &mut Self) -> &mut [T];
+
+    /// Returns the slots if they are not shared.
+    fn get_mut(this: &mut Self) -> Option<&mut [T]>;
+
+    /// Returns true if both point to the same slots.
+    fn ptr_eq(this: &Self, other: &Self) -> bool;
+}
+
+impl<T> private::Sealed for Arc<[T]> {}
+
+impl<T: Clone> SharedSlots<T> for Arc<[T]> {
+    fn make_mut(this: &mut Self) -> &mut [T] {
+        Arc::make_mut(this)
+    }
+
+    fn get_mut(this: &mut Self) -> Option<&mut [T]> {
+        Arc::get_mut(this)
+    }
+
+    fn ptr_eq(this: &Self, other: &Self) -> bool {
+        Arc::ptr_eq(this, other)
+    }
+}
+
+impl<T> private::Sealed for Rc<[T]> {}
+
+impl<T: Clone> SharedSlots<T> for Rc<[T]> {
+    fn make_mut(this: &mut Self) -> &mut [T] {
+        Rc::make_mut(this)
+    }
+
+    fn get_mut(this: &mut Self) -> Option<&mut [T]> {
+        Rc::get_mut(this)
+    }
+
+    fn ptr_eq(this: &Self, other: &Self) -> bool {
+        Rc::ptr_eq(this, other)
+    }
+}
+
+/// A SmartBuffer whose heap segment is shared between clones and only copied when one of them
+/// writes to it. The `N` elements on the stack are still cloned eagerly.
+///
+/// The first `N` elements always live on the stack, like with [`Spill::Split`](crate::spill::Spill::Split).
+/// `P` is the reference-counted pointer, `Arc<[T]>` by default or `Rc<[T]>` with [`RcCowBuffer`].
+///
+/// ```
+/// use smart_buffer::SmartBuffer;
+/// use smart_buffer::cow::CowBuffer;
+///
+/// let mut settings = SmartBuffer::<u32, 2>::new(0, 64);
+/// settings.insert_slice(&[1, 2, 3, 4, 5]);
+/// let snapshot: CowBuffer<u32, 2> = settings.into();
+///
+/// let mut per_request = snapshot.clone();
+/// assert!(per_request.shares_heap_with(&snapshot));
+/// per_request[0] = 10; // on the stack, the heap stays shared
+/// assert!(per_request.shares_heap_with(&snapshot));
+/// per_request[4] = 50; // copies the heap
+/// assert!(!per_request.shares_heap_with(&snapshot));
+/// assert_eq!(snapshot[4], 5);
+/// ```
+pub struct CowBuffer<T, const N:usize, P = Arc<[T]>>
+    where T: Clone,
+        P: SharedSlots<T>
+{
+    s_buf: [T; N],
+    heap: Option<P>,
+    size: usize,
+    default: T,
+}
+
+/// A [`CowBuffer`] sharing its heap segment through an `Rc`.
+pub type RcCowBuffer<T, const N:usize> = CowBuffer<T, N, Rc<[T]>>;
+
+impl<T, const N:usize, P> CowBuffer<T,N,P>
+    where T: Clone,
+        P: SharedSlots<T>
+{
+    /// Creates an empty CowBuffer with no heap, where the default value is set to the value entered.
+    pub fn new(value: T) -> Self{
+        Self{
+            s_buf: core::array::from_fn(|_| value.clone()),
+            heap: None,
+            size: 0,
+            default: value,
+        }
+    }
+
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize{
+        self.size
+    }
+
+    /// Returns true if the CowBuffer holds no elements.
+    pub fn is_empty(&self) -> bool{
+        self.size == 0
+    }
+
+    /// Returns the total number of elements the CowBuffer can hold, stack and heap combined.
+    pub fn capacity(&self) -> usize{
+        N + self.heap.as_deref().map_or(0, <[T]>::len)
+    }
+
+    /// Returns true if both CowBuffers point to the same heap segment.
+    pub fn shares_heap_with(&self, other: &Self) -> bool{
+        match (&self.heap, &other.heap){
+            (Some(a), Some(b)) => P::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    /// Returns the elements as two slices, the part on the stack followed by the part on the heap.
+    pub fn as_slices(&self) -> (&[T], &[T]){
+        let stack = self.size.min(N);
+        let heap = match &self.heap{
+            Some(heap) => &heap[..self.size - stack],
+            None => &[],
+        };
+        (&self.s_buf[..stack], heap)
+    }
+
+    /// Returns the elements as two mutable slices, copying the heap segment first if it is shared.
+    pub fn make_mut(&mut self) -> (&mut [T], &mut [T]){
+        let stack = self.size.min(N);
+        let heap = match &mut self.heap{
+            Some(heap) if self.size > N => &mut P::make_mut(heap)[..self.size - N],
+            _ => &mut [],
+        };
+        (&mut self.s_buf[..stack], heap)
+    }
+
+    /// Returns an iterator over the elements.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> + '_{
+        let (stack, heap) = self.as_slices();
+        stack.iter().chain(heap)
+    }
+
+    /// Safely get a value at an index
+    pub fn get(&self, index:usize) -> Option<&T>{
+        if index >= self.size{
+            return None
+        }
+        if index < N{
+            return Some(&self.s_buf[index])
+        }
+        self.heap.as_deref().map(|heap| &heap[index - N])
+    }
+
+    /// Mutable version of [`get`](Self::get). Copies the heap segment first if the element lives
+    /// there and it is shared.
+    pub fn get_mut(&mut self, index:usize) -> Option<&mut T>{
+        if index >= self.size{
+            return None
+        }
+        if index < N{
+            return Some(&mut self.s_buf[index])
+        }
+        self.heap.as_mut().map(|heap| &mut P::make_mut(heap)[index - N])
+    }
+
+    /// Pushes a value, growing the heap segment if needed. Writing to a shared heap segment copies it.
+    pub fn push(&mut self, value: T){
+        if self.size < N{
+            self.s_buf[self.size] = value;
+        } else {
+            if self.size == self.capacity(){
+                self.grow(self.capacity().max(N).max(4));
+            }
+            let heap = P::make_mut(self.heap.as_mut().unwrap());
+            heap[self.size - N] = value;
+        }
+        self.size += 1;
+    }
+
+    /// Shortens the CowBuffer to `len` elements, setting the removed ones back to the default value.
+    /// A shared heap segment is left as it is rather than copied, its removed slots keep their
+    /// values until they are written to again.
+    pub fn truncate(&mut self, len:usize){
+        if len >= self.size{
+            return
+        }
+        if self.size > N{
+            if let Some(slots) = self.heap.as_mut().and_then(P::get_mut){
+                slots[len.saturating_sub(N)..self.size - N].fill(self.default.clone());
+            }
+        }
+        if len < N{
+            self.s_buf[len..self.size.min(N)].fill(self.default.clone());
+        }
+        self.size = len;
+    }
+
+    /// Adds `additional` slots to the heap segment, moving the elements if it is not shared.
+    fn grow(&mut self, additional:usize){
+        let default = self.default.clone();
+        let padding = repeat_with(|| default.clone()).take(additional);
+        self.heap = Some(match self.heap.take(){
+            None => padding.collect(),
+            Some(mut heap) => match P::get_mut(&mut heap){
+                Some(slots) => slots.iter_mut().map(|slot| mem::replace(slot, default.clone())).chain(padding).collect(),
+                None => heap.iter().cloned().chain(padding).collect(),
+            },
+        });
+    }
+}
+
+impl<T, const N:usize, P> From<SmartBuffer<T,N>> for CowBuffer<T,N,P>
+    where T: Clone,
+        P: SharedSlots<T>
+{
+    /// Moves the elements over, keeping the capacity. The heap segment is allocated once.
+    fn from(buf: SmartBuffer<T,N>) -> Self {
+        let default = buf.default.clone();
+        let heap_slots = buf.capacity().saturating_sub(N);
+        let size = buf.len();
+        let mut elems = buf.into_iter();
+        let s_buf = core::array::from_fn(|_| elems.next().unwrap_or_else(|| default.clone()));
+        let heap = if heap_slots > 0{
+            Some(elems.chain(repeat_with(|| default.clone())).take(heap_slots).collect())
+        } else {
+            None
+        };
+        Self{
+            s_buf,
+            heap,
+            size,
+            default,
+        }
+    }
+}
+
+impl<T, const N:usize, P> From<CowBuffer<T,N,P>> for SmartBuffer<T,N>
+    where T: Clone,
+        P: SharedSlots<T>
+{
+    /// Copies the elements into a SmartBuffer of the same capacity. They are moved rather than
+    /// cloned if the heap segment is not shared.
+    fn from(mut buf: CowBuffer<T,N,P>) -> Self {
+        let mut out = SmartBuffer::from_default(buf.default.clone());
+        out.reserve_exact(buf.capacity());
+        let stack = buf.size.min(N);
+        for elem in &mut buf.s_buf[..stack]{
+            out.push(mem::replace(elem, buf.default.clone()));
+        }
+        let live = buf.size - stack;
+        if let Some(mut heap) = buf.heap.take(){
+            match P::get_mut(&mut heap){
+                Some(slots) => slots[..live].iter_mut().for_each(|elem| out.push(mem::replace(elem, buf.default.clone()))),
+                None => out.insert_slice(&heap[..live]),
+            }
+        }
+        out
+    }
+}
+
+impl<T, const N:usize, P> Clone for CowBuffer<T,N,P>
+    where T: Clone,
+        P: SharedSlots<T>
+{
+    /// Clones the stack, but only bumps the reference count of the heap segment.
+    fn clone(&self) -> Self {
+        Self{
+            s_buf: self.s_buf.clone(),
+            heap: self.heap.clone(),
+            size: self.size,
+            default: self.default.clone(),
+        }
+    }
+}
+
+impl<T, const N:usize, P> Index<usize> for CowBuffer<T,N,P>
+    where T: Clone,
+        P: SharedSlots<T>
+{
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        match self.get(index){
+            Some(elem) => elem,
+            None => panic!("index {} is out of bounds for a CowBuffer of length {}", index, self.size),
+        }
+    }
+}
+
+impl<T, const N:usize, P> IndexMut<usize> for CowBuffer<T,N,P>
+    where T: Clone,
+        P: SharedSlots<T>
+{
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        let size = self.size;
+        match self.get_mut(index){
+            Some(elem) => elem,
+            None => panic!("index {} is out of bounds for a CowBuffer of length {}", index, size),
+        }
+    }
+}
+
+impl<T, const N:usize, P> Debug for CowBuffer<T,N,P>
+    where T: Clone + Debug,
+        P: SharedSlots<T>
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_list()
+            .entries(self.iter())
+            .finish()
+    }
+}
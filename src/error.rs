@@ -0,0 +1,47 @@
+use core::alloc::Layout;
+use core::fmt::{Display, Formatter};
+
+/// The error type for `try_reserve` methods.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum TryReserveError {
+    /// The requested capacity exceeds the maximum a SmartBuffer can hold.
+    CapacityOverflow,
+    /// The allocator refused to hand out memory for `layout`.
+    AllocError {
+        layout: Layout,
+    },
+}
+
+impl Display for TryReserveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            TryReserveError::CapacityOverflow => f.write_str("capacity overflow"),
+            TryReserveError::AllocError { layout } => {
+                write!(f, "memory allocation of {} bytes failed", layout.size())
+            }
+        }
+    }
+}
+
+/// The error type for the `get_*` methods of byte SmartBuffers. The read cursor is left where it was.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReadError {
+    /// The value needs more bytes than are left after the cursor.
+    UnexpectedEnd {
+        needed: usize,
+        available: usize,
+    },
+    /// A LEB128 varint does not fit in the type it is read into.
+    VarintOverflow,
+}
+
+impl Display for ReadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ReadError::UnexpectedEnd { needed, available } => {
+                write!(f, "needed {} bytes but only {} are left", needed, available)
+            }
+            ReadError::VarintOverflow => f.write_str("varint is too long for its type"),
+        }
+    }
+}
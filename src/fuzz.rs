@@ -0,0 +1,46 @@
+use crate::SmartBuffer;
+use crate::spill::Spill;
+
+use arbitrary::{size_hint, Arbitrary, Result, Unstructured};
+
+impl<'a> Arbitrary<'a> for Spill{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self>{
+        Ok(if u.arbitrary()? {
+            Spill::All {unspill_below: u.arbitrary()?}
+        } else {
+            Spill::Split
+        })
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>){
+        size_hint::and(<bool as Arbitrary>::size_hint(depth), <Option<usize> as Arbitrary>::size_hint(depth))
+    }
+}
+
+/// The default value and the spill strategy are generated first, then elements are pushed until
+/// the input runs out, so the heap is allocated and grown the same way as in real use.
+impl<'a, T, const N:usize> Arbitrary<'a> for SmartBuffer<T,N>
+    where T: Arbitrary<'a> + Clone
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self>{
+        let mut buf = Self::from_default(u.arbitrary()?).with_spill(u.arbitrary()?);
+        for elem in u.arbitrary_iter()?{
+            buf.reserve(1);
+            buf.push(elem?);
+        }
+        Ok(buf)
+    }
+
+    fn arbitrary_take_rest(mut u: Unstructured<'a>) -> Result<Self>{
+        let mut buf = Self::from_default(u.arbitrary()?).with_spill(u.arbitrary()?);
+        for elem in u.arbitrary_take_rest_iter()?{
+            buf.reserve(1);
+            buf.push(elem?);
+        }
+        Ok(buf)
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>){
+        size_hint::and(<T as Arbitrary>::size_hint(depth), (1, None))
+    }
+}
@@ -1,10 +1,12 @@
 use crate::SmartBuffer;
+use crate::storage::Storage;
 use core::ops::Index;
 use core::ops::IndexMut;
 
 
-impl<T, const N:usize> Index<usize> for &SmartBuffer<T,N>
-    where T: Clone
+impl<T, const N:usize, S> Index<usize> for &SmartBuffer<T,N,S>
+    where T: Clone,
+        S: Storage<T>
 {
     type Output = T;
 
@@ -13,8 +15,9 @@ impl<T, const N:usize> Index<usize> for &SmartBuffer<T,N>
     }
 }
 
-impl<T, const N:usize> Index<usize> for SmartBuffer<T,N>
-    where T: Clone
+impl<T, const N:usize, S> Index<usize> for SmartBuffer<T,N,S>
+    where T: Clone,
+        S: Storage<T>
 {
     type Output = T;
 
@@ -23,8 +26,9 @@ impl<T, const N:usize> Index<usize> for SmartBuffer<T,N>
     }
 }
 
-impl<T, const N:usize> IndexMut<usize> for SmartBuffer<T,N>
-where T: Clone
+impl<T, const N:usize, S> IndexMut<usize> for SmartBuffer<T,N,S>
+    where T: Clone,
+        S: Storage<T>
 {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
         unsafe {self.get_mut_unchecked(index)}
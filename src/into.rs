@@ -1,13 +1,14 @@
 use crate::SmartBuffer;
+use crate::storage::Storage;
 use alloc::vec::Vec;
-use crate::iter::SmartBufferIter;
 
-impl<T, const N: usize> Into<Vec<T>> for SmartBuffer<T,N>
-    where T: Clone
+impl<T, const N:usize, S> From<SmartBuffer<T,N,S>> for Vec<T>
+    where T: Clone,
+        S: Storage<T>
 {
-    fn into(self) -> Vec<T> {
-        let mut temp = Vec::new();
-        for elem in self{
+    fn from(buf: SmartBuffer<T,N,S>) -> Vec<T> {
+        let mut temp = Vec::with_capacity(buf.get_size());
+        for elem in buf{
             temp.push(elem);
         }
         temp
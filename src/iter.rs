@@ -1,45 +1,53 @@
 use crate::SmartBuffer;
+use crate::storage::{Global, Storage};
 
-use core::mem::size_of;
-use alloc::alloc::{dealloc};
-use crate::__core::iter::Map;
+use core::marker::PhantomData;
 
-impl<T, const N: usize> IntoIterator for SmartBuffer<T,N>
-    where T: Clone
+impl<T, const N:usize, S> SmartBuffer<T,N,S>
+    where T: Clone,
+        S: Storage<T>
+{
+    /// Returns an iterator over the elements.
+    pub fn iter(&self) -> SmartBufferIterRef<'_,T,N,S>{
+        self.into_iter()
+    }
+
+    /// Returns an iterator that allows modifying each element.
+    pub fn iter_mut(&mut self) -> SmartBufferIterRefMut<'_,T,N,S>{
+        self.into_iter()
+    }
+}
+
+impl<T, const N:usize, S> IntoIterator for SmartBuffer<T,N,S>
+    where T: Clone,
+        S: Storage<T>
 {
     type Item = T;
-    type IntoIter = SmartBufferIter<T,N>;
+    type IntoIter = SmartBufferIter<T,N,S>;
     /// Creates a consuming Iterator
-    fn into_iter(mut self) -> Self::IntoIter {
-        let stack_ptr = self.s_buf.as_mut_ptr();
-        let heap_ptr = self.d_buf;
+    fn into_iter(self) -> Self::IntoIter {
         let total_elem = self.size;
 
         Self::IntoIter {
             smart_buffer: self, // Self will be dropped when IntoIter is over
-            stack_ptr,
-            heap_ptr,
             total_elem,
             count: 0
         }
     }
 }
 
-impl<'a, T, const N: usize> IntoIterator for &'a SmartBuffer<T,N>
-    where T: Clone
+impl<'a, T, const N:usize, S> IntoIterator for &'a SmartBuffer<T,N,S>
+    where T: Clone,
+        S: Storage<T>
 {
     type Item = &'a T;
-    type IntoIter = SmartBufferIterRef<'a,T,N>;
+    type IntoIter = SmartBufferIterRef<'a,T,N,S>;
     /// Creates a consuming Iterator
     fn into_iter(self) -> Self::IntoIter {
-        let stack_ptr = self.s_buf.as_ptr();
-        let heap_ptr = self.d_buf;
         let total_elem = self.size;
 
         Self::IntoIter {
             smart_buffer: self,
-            stack_ptr,
-            heap_ptr,
             total_elem,
             count: 0
         }
@@ -48,21 +56,24 @@ impl<'a, T, const N: usize> IntoIterator for &'a SmartBuffer<T,N>
 
 }
 
-impl<'a, T, const N: usize> IntoIterator for &'a mut SmartBuffer<T,N>
-    where T: Clone
+impl<'a, T, const N:usize, S> IntoIterator for &'a mut SmartBuffer<T,N,S>
+    where T: Clone,
+        S: Storage<T>
 {
     type Item = &'a mut T;
-    type IntoIter = SmartBufferIterRefMut<'a,T,N>;
+    type IntoIter = SmartBufferIterRefMut<'a,T,N,S>;
     /// Creates a consuming Iterator
     fn into_iter(self) -> Self::IntoIter {
         let stack_ptr = self.s_buf.as_mut_ptr();
-        let heap_ptr = self.d_buf.clone();
+        let heap_ptr = self.d_buf;
+        let inline = self.inline;
         let total_elem = self.size;
 
         Self::IntoIter {
-            smart_buffer: self,
+            smart_buffer: PhantomData,
             stack_ptr,
             heap_ptr,
+            inline,
             total_elem,
             count: 0
         }
@@ -70,93 +81,84 @@ impl<'a, T, const N: usize> IntoIterator for &'a mut SmartBuffer<T,N>
 }
 
 /// Iterator for SmartBuffer where the SmartBuffer is Consumed
-pub struct SmartBufferIter<T, const N:usize>
-    where T: Clone
+pub struct SmartBufferIter<T, const N:usize, S = Global>
+    where T: Clone,
+        S: Storage<T>
 {
-    smart_buffer: SmartBuffer<T,N>,
-    stack_ptr: *mut T,
-    heap_ptr: Option<*mut T>,
+    smart_buffer: SmartBuffer<T,N,S>,
     total_elem: usize,
     count: usize,
 }
 
-impl<T, const N: usize> Iterator for SmartBufferIter<T,N>
-    where T: Clone
+impl<T, const N:usize, S> Iterator for SmartBufferIter<T,N,S>
+    where T: Clone,
+        S: Storage<T>
 {
     type Item = T;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.count < self.total_elem{
-            if self.count < N{
-                self.count += 1;
-                return unsafe {Some((*((self.stack_ptr as usize + (self.count - 1) * size_of::<T>()) as *mut T)).clone())}
-            }
             self.count += 1;
-            return unsafe {Some((*((self.heap_ptr.unwrap() as usize + (self.count - N - 1) * size_of::<T>()) as *mut T)).clone())}
+            let default = self.smart_buffer.default.clone();
+            // moves the value out, leaving the default behind to be dropped with the buffer
+            return unsafe {Some(core::mem::replace(self.smart_buffer.get_mut_unchecked(self.count - 1), default))}
         }
         None
     }
-
-    // fn map<F>(self, f: F) -> Map<Self, F>
-    // where F: FnMut(Self::Item) -> T
-    // {
-    //     unimplemented!()
-    // }
 }
 
 /// Iterator for SmartBuffer where the SmartBuffer is immutably referenced to
-pub struct SmartBufferIterRef<'a, T, const N:usize>
-    where T: 'a + Clone
+pub struct SmartBufferIterRef<'a, T, const N:usize, S = Global>
+    where T: 'a + Clone,
+        S: Storage<T>
 {
-    smart_buffer: &'a SmartBuffer<T,N>,
-    stack_ptr: *const T,
-    heap_ptr: Option<*mut T>, // will not change values in heap_ptr
+    smart_buffer: &'a SmartBuffer<T,N,S>,
     total_elem: usize,
     count: usize,
 }
 
-impl<'a, T, const N: usize> Iterator for SmartBufferIterRef<'a, T,N>
-    where T: 'a + Clone
+impl<'a, T, const N:usize, S> Iterator for SmartBufferIterRef<'a, T,N,S>
+    where T: 'a + Clone,
+        S: Storage<T>
 {
     type Item = &'a T;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.count < self.total_elem{
-            if self.count < N{
-                self.count += 1;
-                return unsafe {Some(&*((self.stack_ptr as usize + (self.count - 1) * size_of::<T>()) as *const T))}
-            }
             self.count += 1;
-            return unsafe {Some(&*((self.heap_ptr.unwrap() as usize + (self.count - N - 1) * size_of::<T>()) as *const T))}
+            return unsafe {Some(self.smart_buffer.get_unchecked(self.count - 1))}
         }
         None
     }
 }
 
 /// Iterator for SmartBuffer where SmartBuffer is mutably referenced to
-pub struct SmartBufferIterRefMut<'a, T, const N:usize>
-    where T: 'a + Clone
+pub struct SmartBufferIterRefMut<'a, T, const N:usize, S = Global>
+    where T: 'a + Clone,
+        S: Storage<T>
 {
-    smart_buffer: &'a mut SmartBuffer<T,N>,
+    smart_buffer: PhantomData<&'a mut SmartBuffer<T,N,S>>,
     stack_ptr: *mut T,
     heap_ptr: Option<*mut T>, // will not change values in heap_ptr
+    inline: usize,
     total_elem: usize,
     count: usize,
 }
 
-impl<'a, T, const N: usize> Iterator for SmartBufferIterRefMut<'a, T,N>
-    where T: 'a + Clone
+impl<'a, T, const N:usize, S> Iterator for SmartBufferIterRefMut<'a, T,N,S>
+    where T: 'a + Clone,
+        S: Storage<T>
 {
     type Item = &'a mut T;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.count < self.total_elem{
-            if self.count < N{
+            if self.count < self.inline{
                 self.count += 1;
-                return unsafe {Some(&mut *((self.stack_ptr as usize + (self.count - 1) * size_of::<T>()) as *mut T))}
+                return unsafe {Some(&mut *self.stack_ptr.add(self.count - 1))}
             }
             self.count += 1;
-            return unsafe {Some(&mut *((self.heap_ptr.unwrap() as usize + (self.count - N - 1) * size_of::<T>()) as *mut T))}
+            return unsafe {Some(&mut *self.heap_ptr.unwrap().add(self.count - self.inline - 1))}
         }
         None
     }
@@ -1,87 +1,169 @@
 #![no_std]
-#![feature(min_const_generics)]
-#[macro_use]
+#[cfg(feature = "alloc")]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
 #[doc(hidden)]
 pub extern crate core as __core;
 
-use alloc::alloc::{alloc, dealloc, Layout};
-use alloc::vec::Vec;
-use core::mem::size_of;
+#[cfg(feature = "alloc")]
+use alloc::alloc::handle_alloc_error;
+use core::ptr::NonNull;
+use core::{mem, ptr, slice};
 
-use crate::iter::SmartBufferIterRef;
-use alloc::boxed::Box;
 use crate::__core::fmt::{Debug, Formatter};
+use crate::error::TryReserveError;
+use crate::spill::Spill;
+use crate::checkpoint::{Journal, Undo};
+use crate::storage::{Global, Storage};
 
 pub mod iter;
 mod index;
+#[cfg(feature = "alloc")]
 pub mod into;
+#[cfg(feature = "alloc")]
+pub mod cow;
+#[cfg(feature = "alloc")]
+pub mod pool;
+pub mod error;
+pub mod spill;
+pub mod storage;
+pub mod checkpoint;
+pub mod chunks;
+mod algorithms;
+mod codec;
+mod concat;
+mod map;
+pub mod matrix;
+pub mod sentinel;
+pub mod string;
+#[cfg(feature = "rayon")]
+pub mod par;
+#[cfg(feature = "zeroize")]
+pub mod secure;
+#[cfg(feature = "arbitrary")]
+mod fuzz;
+#[cfg(feature = "proptest")]
+pub mod prop;
+#[cfg(feature = "stats")]
+pub mod stats;
 
-#[macro_use]
-#[cfg(test)]
-mod tests {
-    use crate::SmartBuffer;
-    use alloc::string::String;
-    use crate::buf;
-    use alloc::vec::Vec;
-
-    #[test]
-    fn it_works() {
-        let mut buf = buf!(0u32, 5, 10);
-        buf.insert_arr(&[4,9,3,2,1,9,3,2,10,19]);
-        //buf.map(|x| x*2);
-        let mut buf_clone = buf.clone();
-        //println!("{:?}", &buf);
-        //println!("{:?}", &buf);
-        //println!("{:?}", &buf_clone);
-
-        let test = SmartBuffer::from_arr([1u8,4,5,6,7], 5, true);
-        //println!("{:?}", &test);
-    }
+/// Where a slot of a SmartBuffer is stored, see [`SmartBuffer::region_of`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Region {
+    /// The inline array.
+    Stack,
+    /// The heap allocation.
+    Heap,
 }
 
-#[feature(min_const_generics)]
-pub struct SmartBuffer<T, const N:usize>
-    where T: Clone
+pub struct SmartBuffer<T, const N:usize, S = Global>
+    where T: Clone,
+        S: Storage<T>
 {
     s_buf: [T; N],
-    d_buf: Option<*mut T>,
-    layout: Option<Layout>,
+    d_buf: Option<*mut T>, // every slot in the heap is initialised, just like the stack
+    storage: S, // where d_buf comes from, it holds capacity - inline slots
     size: usize,
     capacity: usize,
+    inline: usize, // how many of the leading elements live in s_buf, either N or 0 once spilled
+    spill: Spill,
     default: T, // The zero value
-    cursor: usize,
+    cursor: usize, // where the get_* methods of byte buffers read next, never past the size
+    #[cfg(feature = "zeroize")]
+    wipe: bool, // set by SecureBuffer, wipes heap memory before it is freed
+    #[cfg(feature = "stats")]
+    peak: usize, // the largest size so far, reported when the buffer is dropped
+    journal: Journal<T>, // undo log of the open checkpoints, zero-sized without the checkpoint feature
 }
 
-impl<T, const N:usize> SmartBuffer<T,N>
-    where T: Clone
+impl<T, const N:usize, S> SmartBuffer<T,N,S>
+    where T: Clone,
+        S: Storage<T>
 {
 
-    /// Clears all values to the given default value.
+    /// Sets all values back to the default value and the size to zero. The capacity is kept.
     pub fn clear(&mut self){
-        let default = self.default.clone();
-        for elem in self{
-            *elem = default.clone();
+        self.truncate(0);
+    }
+
+    /// Overwrites every element with a clone of `value`. The size is unchanged.
+    pub fn fill(&mut self, value: T){
+        self.fill_with(|| value.clone());
+    }
+
+    /// Overwrites every element with the result of calling `f`, in order. The size is unchanged.
+    pub fn fill_with<F>(&mut self, mut f: F)
+    where F: FnMut() -> T
+    {
+        for i in 0..self.size{
+            let old = core::mem::replace(unsafe {self.get_mut_unchecked(i)}, f());
+            self.journal.record(Undo::Slot(i, old));
+        }
+    }
+
+    /// Resizes the buffer to `len` elements, appending clones of `value` or truncating.
+    ///
+    /// Growing reserves the room first, so the buffer spills at most once. If the storage runs
+    /// out of room, for example without the `alloc` feature, the size stops at the capacity.
+    pub fn resize(&mut self, len:usize, value: T){
+        self.resize_with(len, || value.clone());
+    }
+
+    /// Resizes the buffer to `len` elements, appending the results of calling `f` or truncating.
+    /// See [`resize`](Self::resize).
+    pub fn resize_with<F>(&mut self, len:usize, mut f: F)
+    where F: FnMut() -> T
+    {
+        if len <= self.size{
+            self.truncate(len);
+            return
+        }
+        self.reserve_what_fits(len - self.size);
+        while self.size < len.min(self.capacity){
+            self.push(f());
         }
     }
 
     /// Safely push a value into the SmartBuffer
     pub fn push(&mut self, other: T){
-        if self.size < N{ // insert into stack
+        self.journal.record(Undo::Size(self.size));
+        if self.size < self.inline{ // insert into stack
             self.s_buf[self.size] = other;
             self.size += 1;
         } else if self.size < self.capacity{ // insert into heap
-            unsafe {*((self.d_buf.unwrap() as usize + (self.size - N) * size_of::<T>()) as *mut T) = other};
+            unsafe {*self.d_buf.unwrap().add(self.size - self.inline) = other};
             self.size += 1;
         }
+        self.note_peak();
     }
 
-    /// Sets the size of the buffer (does not reduce capacity)
+    /// Sets the size of the buffer (does not reduce capacity). Does nothing if `size` is past the capacity.
     pub fn set_size(&mut self, size:usize){
-        if self.size < self.capacity{
+        if size <= self.capacity{
+            self.journal.record(Undo::Size(self.size));
             self.size = size;
+            self.cursor = self.cursor.min(size);
+            self.note_peak();
+            self.maybe_unspill();
+        }
+    }
+
+    /// Shortens the buffer to `len` elements, setting the removed ones back to the default value.
+    /// Does nothing if `len` is not below the size.
+    pub fn truncate(&mut self, len:usize){
+        if self.size > len{
+            self.journal.record(Undo::Size(self.size));
+        }
+        while self.size > len{
+            self.size -= 1;
+            let default = self.default.clone();
+            let old = core::mem::replace(unsafe {self.get_mut_unchecked(self.size)}, default);
+            self.journal.record(Undo::Slot(self.size, old));
         }
+        self.cursor = self.cursor.min(self.size);
+        self.maybe_unspill();
     }
 
     /// Safely inserts a slice of data, starting at the size.
@@ -109,43 +191,137 @@ impl<T, const N:usize> SmartBuffer<T,N>
 
     /// Safely insert a value into the SmartBuffer
     pub fn insert(&mut self, other: T, index: usize){
-        if index < N{ // insert into stack
-            self.s_buf[index] = other;
-            if index > self.size{
-                self.size = index;
+        if index < self.capacity{
+            self.journal.record(Undo::Size(self.size));
+        }
+        if index < self.inline{ // insert into stack
+            let old = core::mem::replace(&mut self.s_buf[index], other);
+            self.journal.record(Undo::Slot(index, old));
+            if index >= self.size{
+                self.size = index + 1;
             }
         } else if index < self.capacity{ // insert into heap
-            unsafe {*((self.d_buf.unwrap() as usize + (index - N) * size_of::<T>()) as *mut T) = other};
-            if index > self.size{
-                self.size = index;
+            let old = core::mem::replace(unsafe {&mut *self.d_buf.unwrap().add(index - self.inline)}, other);
+            self.journal.record(Undo::Slot(index, old));
+            if index >= self.size{
+                self.size = index + 1;
             }
         }
+        self.note_peak();
+    }
+
+    /// Safely remove the value at an index, shifting everything after it to the left. The slot
+    /// freed at the end is set back to the default value.
+    pub fn remove(&mut self, index:usize) -> Option<T>{
+        if index >= self.size{
+            return None
+        }
+        for i in index..(self.size - 1){
+            self.swap_slots(i, i + 1);
+        }
+        self.size -= 1;
+        let default = self.default.clone();
+        let removed = core::mem::replace(unsafe {self.get_mut_unchecked(self.size)}, default);
+        if self.journal.is_recording(){
+            self.journal.record(Undo::Removed(index, removed.clone()));
+        }
+        self.cursor = self.cursor.min(self.size);
+        self.maybe_unspill();
+        Some(removed)
     }
 
     /// Safely get a value at an index
     pub fn get(&self, index:usize) -> Option<&T> {
-        if index < N {
+        if index < self.inline {
             return Some(&(self.s_buf[index]))
         } else if index < self.capacity {
-            return unsafe { Some(&*((self.d_buf.unwrap() as usize + (index - N) * size_of::<T>()) as *const T)) }
+            return unsafe { Some(&*self.d_buf.unwrap().add(index - self.inline)) }
         }
         None
     }
 
+    /// Returns the elements as two slices, the part on the stack followed by the part on the heap.
+    pub fn as_slices(&self) -> (&[T], &[T]){
+        let stack = self.size.min(self.inline);
+        let heap = match self.d_buf{
+            Some(ptr) => unsafe {slice::from_raw_parts(ptr, self.size - stack)},
+            None => &[],
+        };
+        (&self.s_buf[..stack], heap)
+    }
+
+    /// Returns the elements as two mutable slices, the part on the stack followed by the part on the heap.
+    pub fn as_mut_slices(&mut self) -> (&mut [T], &mut [T]){
+        let stack = self.size.min(self.inline);
+        let heap = match self.d_buf{
+            Some(ptr) => unsafe {slice::from_raw_parts_mut(ptr, self.size - stack)},
+            None => &mut [],
+        };
+        (&mut self.s_buf[..stack], heap)
+    }
+
+    /// Returns the elements as one slice if they are contiguous. This is always the case with
+    /// [`Spill::All`], and with [`Spill::Split`] as long as the SmartBuffer has not outgrown its stack.
+    pub fn as_slice(&self) -> Option<&[T]>{
+        match self.as_slices(){
+            (stack, []) => Some(stack),
+            ([], heap) => Some(heap),
+            _ => None,
+        }
+    }
+
+    /// Mutable version of [`as_slice`](Self::as_slice).
+    pub fn as_mut_slice(&mut self) -> Option<&mut [T]>{
+        match self.as_mut_slices(){
+            (stack, []) => Some(stack),
+            ([], heap) => Some(heap),
+            _ => None,
+        }
+    }
+
     /// Unsafely get a reference to a value at an index. An index too large will result in a fault
+    ///
+    /// # Safety
+    /// `index` must be less than the capacity of the SmartBuffer.
     pub unsafe fn get_unchecked(&self, index:usize) -> &T{
-        if index < N{
+        if index < self.inline{
             return &self.s_buf[index];
         }
-        &*((self.d_buf.unwrap() as usize + (index - N) * size_of::<T>()) as *const T)
+        &*self.d_buf.unwrap().add(index - self.inline)
     }
 
     /// Unsafely get a mutable
+    ///
+    /// # Safety
+    /// `index` must be less than the capacity of the SmartBuffer.
     pub unsafe fn get_mut_unchecked(&mut self, index:usize) -> &mut T{
-        if index < N{
+        if index < self.inline{
             return &mut self.s_buf[index]
         }
-        &mut *((self.d_buf.unwrap() as usize + (index - N) * size_of::<T>()) as *mut T)
+        &mut *self.d_buf.unwrap().add(index - self.inline)
+    }
+
+    /// Remembers the size for the peak length reported by the `stats` feature.
+    #[inline]
+    fn note_peak(&mut self){
+        #[cfg(feature = "stats")]
+        {
+            self.peak = self.peak.max(self.size);
+        }
+    }
+
+    /// Swaps the values in two slots, both of which must be below the capacity.
+    pub(crate) fn swap_slots(&mut self, a:usize, b:usize){
+        let inline = self.inline;
+        match (a < inline, b < inline){
+            (true, true) => self.s_buf.swap(a, b),
+            (false, false) => unsafe {
+                let heap = self.d_buf.unwrap();
+                ptr::swap(heap.add(a - inline), heap.add(b - inline))
+            },
+            (true, false) => unsafe {ptr::swap(&mut self.s_buf[a], self.d_buf.unwrap().add(b - inline))},
+            (false, true) => self.swap_slots(b, a),
+        }
     }
 
 
@@ -153,16 +329,229 @@ impl<T, const N:usize> SmartBuffer<T,N>
         &mut self as *mut Self
     }
 
-    /// Safely allocate extra heap memory
-    pub(crate) fn allocate(&mut self, elements:usize){
-        let layout = Layout::from_size_align(elements*size_of::<T>(), 1);
-        if let Ok(layout) = layout{
-            let ptr = unsafe {alloc(layout) as *mut T};
-            self.capacity += layout.size()/size_of::<T>();
-            self.layout = Some(layout);
-            self.d_buf = Some(ptr);
+    /// Returns the total number of elements the SmartBuffer can hold, stack and heap combined.
+    pub const fn capacity(&self) -> usize{
+        self.capacity
+    }
+
+    /// Returns how many more elements can be pushed before the SmartBuffer is full.
+    pub const fn spare_capacity(&self) -> usize{
+        self.capacity - self.size
+    }
+
+    /// Reserves room for at least `additional` more elements past the size. The heap is grown
+    /// geometrically, so repeated calls are amortized.
+    ///
+    /// # Panics
+    /// Panics if the new capacity overflows or the storage has no room for it, which without the
+    /// `alloc` feature is any capacity past `N`. Aborts if the allocation fails.
+    pub fn reserve(&mut self, additional:usize){
+        handle_reserve(self.try_reserve(additional));
+    }
+
+    /// Reserves room for exactly `additional` more elements past the size.
+    ///
+    /// # Panics
+    /// Panics if the new capacity overflows or the storage has no room for it, which without the
+    /// `alloc` feature is any capacity past `N`. Aborts if the allocation fails.
+    pub fn reserve_exact(&mut self, additional:usize){
+        handle_reserve(self.try_reserve_exact(additional));
+    }
+
+    /// Fallible version of [`reserve`](Self::reserve). On error the SmartBuffer is left untouched.
+    pub fn try_reserve(&mut self, additional:usize) -> Result<(), TryReserveError>{
+        let required = self.size.checked_add(additional).ok_or(TryReserveError::CapacityOverflow)?;
+        if required <= self.capacity{
+            return Ok(())
+        }
+        let target = required.max(self.capacity.saturating_mul(2));
+        let slots = self.heap_slots(target).min(self.storage.max_slots()).max(self.heap_slots(required));
+        self.allocate(slots)
+    }
+
+    /// Fallible version of [`reserve_exact`](Self::reserve_exact). On error the SmartBuffer is left untouched.
+    pub fn try_reserve_exact(&mut self, additional:usize) -> Result<(), TryReserveError>{
+        let required = self.size.checked_add(additional).ok_or(TryReserveError::CapacityOverflow)?;
+        if required <= self.capacity{
+            return Ok(())
+        }
+        self.allocate(self.heap_slots(required))
+    }
+
+    /// Reserves room for `additional` more elements, or as many as the storage can hold. Only
+    /// allocation failures are fatal, so callers can fill whatever room there is.
+    pub(crate) fn reserve_what_fits(&mut self, additional:usize){
+        let result = match self.try_reserve(additional){
+            Err(TryReserveError::CapacityOverflow) => {
+                // settle for the largest region the storage hands out
+                let most = self.storage.max_slots();
+                let grows = most > self.heap_capacity() && (matches!(self.spill, Spill::Split) || most > N);
+                if grows{
+                    self.allocate(most)
+                } else {
+                    Ok(())
+                }
+            }
+            result => result,
+        };
+        if let Err(err @ TryReserveError::AllocError {..}) = result{
+            handle_reserve(Err(err));
+        }
+    }
+
+    /// Shrinks the capacity down to `min_capacity`, but never below the size. Once everything fits
+    /// on the stack the heap is released entirely.
+    pub fn shrink_to(&mut self, min_capacity:usize){
+        let target = min_capacity.max(self.size);
+        if target < self.capacity{
+            // a failed shrink leaves the old heap in place, which is still valid
+            let _ = self.allocate(self.heap_slots(target));
+        }
+    }
+
+    /// Shrinks the capacity as much as possible. Releases the heap if the data fits on the stack.
+    pub fn shrink_to_fit(&mut self){
+        self.shrink_to(0);
+    }
+
+    /// How many heap slots are needed for a total capacity of `capacity`.
+    fn heap_slots(&self, capacity:usize) -> usize{
+        match self.spill{
+            Spill::Split => capacity.saturating_sub(N),
+            Spill::All {..} if capacity <= N => 0,
+            Spill::All {..} => capacity,
+        }
+    }
+
+    /// Resizes the heap to exactly `elements` slots, moving the kept values over and filling new
+    /// slots with the default value. Resizing to zero releases the heap.
+    pub(crate) fn allocate(&mut self, elements:usize) -> Result<(), TryReserveError>{
+        let old = self.capacity - self.inline;
+        if elements == old{
+            return Ok(())
+        }
+        N.checked_add(elements).ok_or(TryReserveError::CapacityOverflow)?;
+        if elements == 0{
+            self.release();
+            return Ok(())
+        }
+
+        let kept = old.min(elements);
+        let grown_in_place = match self.d_buf{
+            Some(old_ptr) if elements > old => unsafe {self.storage.grow_in_place(NonNull::new_unchecked(old_ptr), old, elements)},
+            _ => false,
+        };
+        let new_ptr = match self.d_buf{
+            Some(old_ptr) if grown_in_place => old_ptr,
+            d_buf => {
+                let new_ptr = self.storage.allocate(elements)?.as_ptr();
+                if let Some(old_ptr) = d_buf{
+                    self.capacity = self.inline + kept;
+                    let wipe = self.wipes();
+                    unsafe {
+                        ptr::drop_in_place(ptr::slice_from_raw_parts_mut(old_ptr.add(kept), old - kept));
+                        ptr::copy_nonoverlapping(old_ptr, new_ptr, kept);
+                        free(&mut self.storage, old_ptr, old, wipe);
+                    }
+                }
+                new_ptr
+            }
+        };
+        self.d_buf = Some(new_ptr);
+        self.capacity = self.inline + kept;
+        self.size = self.size.min(self.capacity);
+        self.cursor = self.cursor.min(self.size);
+
+        // capacity only counts a slot once it holds a value, so a panicking clone leaves us consistent
+        while self.capacity - self.inline < elements{
+            unsafe {new_ptr.add(self.capacity - self.inline).write(self.default.clone())};
+            self.capacity += 1;
+        }
+
+        #[cfg(feature = "stats")]
+        stats::record_allocate::<T, N>(self.size, old, elements, elements * mem::size_of::<T>());
+
+        if matches!(self.spill, Spill::All {..}) && self.inline == N{
+            self.spill_to_heap();
+        }
+        Ok(())
+    }
+
+    /// Moves any spilled elements back onto the stack, then drops everything on the heap and frees it.
+    pub(crate) fn release(&mut self){
+        if self.d_buf.is_some() && self.inline != N{
+            self.unspill();
+        }
+        self.free_heap();
+    }
+
+    /// Whether heap memory is wiped before it is freed.
+    fn wipes(&self) -> bool{
+        #[cfg(feature = "zeroize")]
+        return self.wipe;
+        #[cfg(not(feature = "zeroize"))]
+        return false;
+    }
+
+    /// Drops everything on the heap and frees it.
+    fn free_heap(&mut self){
+        if let Some(ptr) = self.d_buf.take(){
+            let elements = self.capacity - self.inline;
+            self.inline = N;
+            self.capacity = N;
+            self.size = self.size.min(N);
+            self.cursor = self.cursor.min(self.size);
+            let wipe = self.wipes();
+            unsafe {
+                ptr::drop_in_place(ptr::slice_from_raw_parts_mut(ptr, elements));
+                free(&mut self.storage, ptr, elements, wipe);
+            }
+        }
+    }
+
+    /// Creates an empty SmartBuffer whose overflow region comes from `storage`, where the default
+    /// value is set to the value entered. Room for `len` elements is reserved up front.
+    ///
+    /// # Panics
+    /// Panics if `storage` cannot hold the elements past the stack.
+    pub fn new_in(value: T, len:usize, storage: S) -> Self{
+        let mut buf = Self::from_default_in(value, storage);
+        if N < len{
+            handle_reserve(buf.allocate(buf.heap_slots(len)));
+        }
+        buf
+    }
+
+    /// Creates an empty SmartBuffer with no heap on `storage`, filled with clones of `default`.
+    pub(crate) fn from_default_in(default: T, storage: S) -> Self{
+        Self{
+            s_buf: core::array::from_fn(|_| default.clone()),
+            d_buf: None,
+            storage,
+            size: 0,
+            capacity: N,
+            inline: N,
+            spill: Spill::Split,
+            default,
+            cursor: 0,
+            #[cfg(feature = "zeroize")]
+            wipe: false,
+            #[cfg(feature = "stats")]
+            peak: 0,
+            journal: Journal::new(),
         }
     }
+
+    /// Returns the storage the overflow region comes from.
+    pub fn storage(&self) -> &S{
+        &self.storage
+    }
+}
+
+/// Constructors for SmartBuffers on the global allocator.
+impl<T, const N:usize> SmartBuffer<T,N>
+    where T: Clone
+{
     // creates a SmartBuffer where the default value is set to the value entered
     pub fn new(value: T, len:usize) -> Self
     where T: Copy + Clone
@@ -170,20 +559,87 @@ impl<T, const N:usize> SmartBuffer<T,N>
         let mut buf = Self{
             s_buf: [value; N],
             d_buf: None,
-            layout: None,
+            storage: Global,
             size: 0,
             capacity: N,
+            inline: N,
+            spill: Spill::Split,
             default: value,
             cursor: 0,
+            #[cfg(feature = "zeroize")]
+            wipe: false,
+            #[cfg(feature = "stats")]
+            peak: 0,
+            journal: Journal::new(),
         };
 
-        if N < len{
-            buf.allocate(len - N);
+        if N < len && cfg!(feature = "alloc"){ // without a heap the capacity stays at N
+            handle_reserve(buf.allocate(buf.heap_slots(len)));
         }
         buf
     }
 
 
+    /// Creates an empty SmartBuffer with no heap, filled with and defaulting to `value`.
+    /// Usable in `const` and `static` items.
+    ///
+    /// ```
+    /// use smart_buffer::SmartBuffer;
+    ///
+    /// static mut LOG: SmartBuffer<u32, 64> = SmartBuffer::new_const(0);
+    /// ```
+    pub const fn new_const(value: T) -> Self
+    where T: Copy
+    {
+        Self{
+            s_buf: [value; N],
+            d_buf: None,
+            storage: Global,
+            size: 0,
+            capacity: N,
+            inline: N,
+            spill: Spill::Split,
+            default: value,
+            cursor: 0,
+            #[cfg(feature = "zeroize")]
+            wipe: false,
+            #[cfg(feature = "stats")]
+            peak: 0,
+            journal: Journal::new(),
+        }
+    }
+
+    /// Creates a full SmartBuffer with no heap from an array. Like [`from_arr`](Self::from_arr),
+    /// the first element becomes the default value. Usable in `const` and `static` items.
+    ///
+    /// ```
+    /// use smart_buffer::SmartBuffer;
+    ///
+    /// const PRIMES: SmartBuffer<u8, 4> = SmartBuffer::from_array_const([2, 3, 5, 7]);
+    /// assert_eq!(PRIMES[3], 7);
+    /// ```
+    pub const fn from_array_const(buf:[T; N]) -> Self
+    where T: Copy
+    {
+        Self{
+            s_buf: buf,
+            d_buf: None,
+            storage: Global,
+            size: N,
+            capacity: N,
+            inline: N,
+            spill: Spill::Split,
+            default: buf[0],
+            cursor: 0,
+            #[cfg(feature = "zeroize")]
+            wipe: false,
+            #[cfg(feature = "stats")]
+            peak: N,
+            journal: Journal::new(),
+        }
+    }
+
+
     // pub fn from_vec(buf:Vec<T>, len: usize, set_size:bool) -> Self{
     //     let temp_buf = [T; N]; // array of defined length;
     //
@@ -201,30 +657,173 @@ impl<T, const N:usize> SmartBuffer<T,N>
         let mut buf = Self{
             s_buf: buf,
             d_buf: None,
-            layout: None,
+            storage: Global,
             size: if set_size { N } else { 0 },
             capacity: N,
+            inline: N,
+            spill: Spill::Split,
             default: def,
             cursor: 0,
+            #[cfg(feature = "zeroize")]
+            wipe: false,
+            #[cfg(feature = "stats")]
+            peak: if set_size { N } else { 0 },
+            journal: Journal::new(),
         };
 
-        if N < len{
-            buf.allocate(len - N);
+        if N < len && cfg!(feature = "alloc"){ // without a heap the capacity stays at N
+            handle_reserve(buf.allocate(buf.heap_slots(len)));
+        }
+        buf
+    }
+
+    /// Used by `buf![a, b, c; N]`. The first element is the default value.
+    #[doc(hidden)]
+    pub fn __from_list<const M:usize>(elems:[T; M]) -> Self{
+        let mut buf = Self::from_default(elems[0].clone());
+        if N < M && cfg!(feature = "alloc"){
+            handle_reserve(buf.allocate(buf.heap_slots(M)));
+        }
+        for elem in elems{
+            buf.push(elem);
+        }
+        buf
+    }
+
+    /// Creates a SmartBuffer holding `len` clones of `value`, which is also the default value.
+    /// Without the `alloc` feature the size stops at `N`.
+    ///
+    /// ```
+    /// use smart_buffer::SmartBuffer;
+    ///
+    /// let row = SmartBuffer::<f32, 4>::repeat(1.0, 3);
+    /// assert_eq!(row.len(), 3);
+    /// assert!(!row.is_spilled());
+    /// ```
+    pub fn repeat(value: T, len:usize) -> Self{
+        let mut buf = Self::from_default(value);
+        if N < len && cfg!(feature = "alloc"){
+            handle_reserve(buf.allocate(buf.heap_slots(len)));
+        }
+        buf.set_size(len.min(buf.capacity));
+        buf
+    }
+
+    /// Creates a SmartBuffer of `len` elements, where element `i` is `f(i)`. `T::default()` is
+    /// the default value. Without the `alloc` feature the size stops at `N`, and `f` is only
+    /// called for the elements that fit.
+    ///
+    /// ```
+    /// use smart_buffer::SmartBuffer;
+    ///
+    /// let squares = SmartBuffer::<u64, 8>::from_fn(5, |i| (i * i) as u64);
+    /// assert_eq!(squares[4], 16);
+    /// ```
+    pub fn from_fn<F>(len:usize, mut f: F) -> Self
+    where T: Default,
+        F: FnMut(usize) -> T
+    {
+        let mut buf = Self::from_default(T::default());
+        if N < len && cfg!(feature = "alloc"){
+            handle_reserve(buf.allocate(buf.heap_slots(len)));
+        }
+        for i in 0..len.min(buf.capacity){
+            buf.push(f(i));
         }
         buf
     }
 
+    /// Creates an empty SmartBuffer with no heap, filled with clones of `default`.
+    pub(crate) fn from_default(default: T) -> Self{
+        Self::from_default_in(default, Global)
+    }
+}
+
+impl<T, const N:usize, S> SmartBuffer<T,N,S>
+    where T: Clone,
+        S: Storage<T>
+{
 
     /// Get the size of the data that has been pushed into the SmartBuffer.
     pub fn get_size(&self) -> usize{
         self.size
     }
 
+    /// Returns the number of elements in the SmartBuffer, the same as [`get_size`](Self::get_size).
+    pub const fn len(&self) -> usize{
+        self.size
+    }
+
+    /// Returns true if the SmartBuffer holds no elements.
+    pub const fn is_empty(&self) -> bool{
+        self.size == 0
+    }
+
+    /// Returns true if elements live on the heap: with [`Spill::Split`] once the size passes `N`,
+    /// with [`Spill::All`] as soon as everything has moved to the heap.
+    pub const fn is_spilled(&self) -> bool{
+        self.inline != N || self.size > N
+    }
+
+    /// Returns the number of elements stored on the stack.
+    pub const fn inline_len(&self) -> usize{
+        if self.size < self.inline { self.size } else { self.inline }
+    }
+
+    /// Returns the number of elements stored on the heap.
+    pub const fn heap_len(&self) -> usize{
+        self.size - self.inline_len()
+    }
+
+    /// Returns the number of slots on the stack, `N`. They are unused once everything has spilled
+    /// with [`Spill::All`].
+    pub const fn inline_capacity(&self) -> usize{
+        N
+    }
+
+    /// Returns the number of slots on the heap.
+    pub const fn heap_capacity(&self) -> usize{
+        self.capacity - self.inline
+    }
+
+    /// Returns the size of the heap allocation in bytes.
+    pub fn heap_bytes(&self) -> usize{
+        self.heap_capacity() * mem::size_of::<T>()
+    }
+
+    /// Returns the memory used by the SmartBuffer in bytes, the struct itself plus the heap.
+    pub fn total_bytes(&self) -> usize{
+        core::mem::size_of::<Self>() + self.heap_bytes()
+    }
+
+    /// Returns where the slot at `index` is stored, or `None` if it is past the capacity.
+    ///
+    /// ```
+    /// use smart_buffer::{Region, SmartBuffer};
+    ///
+    /// # #[cfg(feature = "alloc")] {
+    /// let buf = SmartBuffer::<u8, 4>::new(0, 6);
+    /// assert_eq!(buf.region_of(3), Some(Region::Stack));
+    /// assert_eq!(buf.region_of(4), Some(Region::Heap));
+    /// assert_eq!(buf.region_of(6), None);
+    /// # }
+    /// ```
+    pub const fn region_of(&self, index:usize) -> Option<Region>{
+        if index < self.inline{
+            Some(Region::Stack)
+        } else if index < self.capacity{
+            Some(Region::Heap)
+        } else {
+            None
+        }
+    }
+
     /// Applies a function to each element in the buffer without consuming the buffer.
     pub fn map<F>(&mut self, mut f: F)
     where T: Clone + Copy,
         F: FnMut(T) -> T
     {
+        self.record_slots(0..self.size);
         for i in 0..self.size{
             self[i] = f(self[i])
         }
@@ -232,109 +831,191 @@ impl<T, const N:usize> SmartBuffer<T,N>
 
     /// Shift Elements Left
     ///
-    /// - BUG: Do not shift more than the size of the stack, any more would cause a panic. If a shift of more than the size of the stack is required,
-    /// call this function multiple times.
+    /// Every slot moves `count` places towards the front. The first `count` values are dropped
+    /// and the slots freed at the end are set to the default value.
     pub fn shl(&mut self, count:usize){
-        self.s_buf.rotate_left(count);
-
-        if let Some(ptr) = self.d_buf{
-            for i in 0..count{
-                self.s_buf[N - count + i] = unsafe {(*ptr.offset(i as isize)).clone()}; // copies stuff
-            }
-            for i in 0..(self.capacity - N){
-                if i + count < self.capacity - N{
-                    unsafe { *ptr.offset(i as isize) = (*ptr.offset((i + count) as isize)).clone()}; // shifts left
-                }
-            }
-            for i in 0..count{
-                unsafe { (*ptr.offset((self.capacity - N - count + i) as isize)) = self.default.clone()}; // sets upper bits to zero
-            }
-        } else {
-            for i in 0..count{
-                self.s_buf[N-count+i] = self.default.clone(); // sets upper bits to default values
-            }
+        let count = count.min(self.capacity);
+        self.record_slots(0..self.capacity);
+        for i in count..self.capacity{
+            self.swap_slots(i - count, i);
+        }
+        for i in (self.capacity - count)..self.capacity{
+            let default = self.default.clone();
+            unsafe {*self.get_mut_unchecked(i) = default}; // sets upper bits to default values
         }
     }
 
     /// Shift Elements Right
     ///
-    /// - BUG: Do not shift more than the size of the stack, any more would cause a panic. If a shift of more than the size of the stack is required,
-    ///  call this function multiple times.
+    /// Every slot moves `count` places towards the end. The last `count` values are dropped
+    /// and the slots freed at the front are set to the default value.
     pub fn shr(&mut self, count:usize){
-
-        if let Some(ptr) = self.d_buf{
-            for i in 0..(self.capacity - N){
-                if (self.capacity as i32 - N as i32 - i as i32- count as i32 - 1) >= 0{
-                    unsafe{ *ptr.offset((self.capacity - N - i - 1) as isize) = (*ptr.offset((self.capacity - N - i - count - 1) as isize)).clone()};
-                }
-            }
-            for i in 0..count{
-                unsafe { (*ptr.offset(i as isize)) = self.s_buf[(N as i32 - count as i32 + i as i32) as usize].clone()};
-            }
+        let count = count.min(self.capacity);
+        self.record_slots(0..self.capacity);
+        for i in (count..self.capacity).rev(){
+            self.swap_slots(i - count, i);
         }
-
-        self.s_buf.rotate_right(count);
-
         for i in 0..count{
-            self.s_buf[i] = self.default.clone();
+            let default = self.default.clone();
+            unsafe {*self.get_mut_unchecked(i) = default};
         }
     }
 
 }
 
 
-impl<T, const N:usize> SmartBuffer<T,N>
-    where T:Clone + PartialEq
+impl<T, const N:usize, S> SmartBuffer<T,N,S>
+    where T:Clone + PartialEq,
+        S: Storage<T>
 {
-    /// Recalculates the size
+    /// Recalculates the size as the number of slots before the first one equal to the default
+    /// value, or the capacity if there is none.
+    #[deprecated(note = "use `set_len_from_sentinel`, which takes the sentinel explicitly")]
     pub fn calc_size(&mut self){
         let default = self.default.clone();
+        if self.set_len_from_sentinel(&default).is_none(){
+            self.set_size(self.capacity);
+        }
+    }
+}
 
-        let mut size = 0;
-        for elem in &*self{
-            if *elem == default{
-                break;
-            }
-            size += 1;
+/// Gives a heap region of `slots` slots made by `allocate` back to its storage, zeroing it first
+/// if `wipe` is set.
+unsafe fn free<T, S: Storage<T>>(storage: &mut S, ptr: *mut T, slots: usize, wipe: bool){
+    if wipe{
+        let bytes = ptr as *mut u8;
+        for i in 0..slots * mem::size_of::<T>(){
+            ptr::write_volatile(bytes.add(i), 0);
         }
-        self.set_size(size + 1);
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
     }
+    storage.deallocate(NonNull::new_unchecked(ptr), slots);
 }
 
+/// The parts of a SmartBuffer on the global allocator, taken apart by `into_raw_parts`. Whoever
+/// holds them owns the elements and the heap.
+pub(crate) struct RawParts<T, const N:usize>{
+    pub(crate) s_buf: [T; N],
+    pub(crate) d_buf: Option<*mut T>,
+    pub(crate) size: usize,
+    pub(crate) capacity: usize,
+    pub(crate) inline: usize,
+    pub(crate) spill: Spill,
+    pub(crate) cursor: usize,
+    pub(crate) wipe: bool,
+}
 
-impl<T, const N:usize> Drop for SmartBuffer<T,N>
+impl<T, const N:usize> SmartBuffer<T,N>
     where T: Clone
 {
-    fn drop(&mut self) {
-        if let Some(ptr) = self.d_buf{
-            unsafe {dealloc(ptr as *mut u8, self.layout.unwrap())};
+    /// Takes the SmartBuffer apart without dropping its elements or freeing its heap, so they can
+    /// be handed on. It counts as a drop for the stats.
+    pub(crate) fn into_raw_parts(self) -> RawParts<T,N>{
+        #[cfg(feature = "stats")]
+        stats::record_drop::<T, N>(self.peak.max(self.size));
+        let wipe = self.wipes();
+        let this = mem::ManuallyDrop::new(self);
+        // `this` is never used again, so every field is read out exactly once and owned from here on
+        let (s_buf, default, journal) = unsafe {(ptr::read(&this.s_buf), ptr::read(&this.default), ptr::read(&this.journal))};
+        drop((default, journal));
+        RawParts{
+            s_buf,
+            d_buf: this.d_buf,
+            size: this.size,
+            capacity: this.capacity,
+            inline: this.inline,
+            spill: this.spill,
+            cursor: this.cursor,
+            wipe,
         }
     }
 }
 
-impl<T, const N:usize> Debug for SmartBuffer<T,N>
-    where T: Clone + Debug
+/// Turns a failed reservation into a panic, or an abort for allocation failures, like `Vec` does.
+fn handle_reserve(result: Result<(), TryReserveError>){
+    match result{
+        Ok(()) => {}
+        Err(TryReserveError::CapacityOverflow) => panic!("capacity overflow"),
+        #[cfg(feature = "alloc")]
+        Err(TryReserveError::AllocError {layout}) => handle_alloc_error(layout),
+        #[cfg(not(feature = "alloc"))]
+        Err(TryReserveError::AllocError {..}) => panic!("allocation failed"),
+    }
+}
+
+
+// The heap is owned exclusively, just like the stack.
+unsafe impl<T, const N:usize, S> Send for SmartBuffer<T,N,S>
+    where T: Clone + Send,
+        S: Storage<T> + Send
+{}
+
+unsafe impl<T, const N:usize, S> Sync for SmartBuffer<T,N,S>
+    where T: Clone + Sync,
+        S: Storage<T> + Sync
+{}
+
+impl<T, const N:usize, S> Drop for SmartBuffer<T,N,S>
+    where T: Clone,
+        S: Storage<T>
+{
+    fn drop(&mut self) {
+        #[cfg(feature = "stats")]
+        stats::record_drop::<T, N>(self.peak.max(self.size));
+        self.free_heap();
+    }
+}
+
+impl<T, const N:usize, S> Debug for SmartBuffer<T,N,S>
+    where T: Clone + Debug,
+        S: Storage<T>
 {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         f.debug_list()
-            .entries(self.into_iter())
+            .entries(self)
             .finish()
     }
 }
 
-impl<T, const N:usize> Clone for SmartBuffer<T,N>
-    where T: Clone
+impl<T, const N:usize> Default for SmartBuffer<T,N>
+    where T: Clone + Default
 {
-    fn clone(&self) -> Self {
-        let mut temp_buf = Self::from_arr(self.s_buf.clone(), self.capacity, true); // clones the items in the stack.
-        if let Some(ptr) = self.d_buf{
-            temp_buf.allocate(self.capacity - N); // temp_buf will now have memory on the heap
+    /// Creates an empty SmartBuffer with no heap, where the default value is `T::default()`.
+    fn default() -> Self {
+        Self::from_default(T::default())
+    }
+}
 
-            unsafe {
-                core::intrinsics::copy(ptr, temp_buf.d_buf.unwrap(), self.capacity - N);
+impl<T, const N:usize, S> Clone for SmartBuffer<T,N,S>
+    where T: Clone,
+        S: Storage<T> + Clone
+{
+    /// Clones the elements onto a clone of the storage.
+    fn clone(&self) -> Self {
+        let mut temp_buf = Self{
+            s_buf: self.s_buf.clone(), // clones the items in the stack.
+            d_buf: None,
+            storage: self.storage.clone(),
+            size: 0,
+            capacity: N,
+            inline: N,
+            spill: self.spill,
+            default: self.default.clone(),
+            cursor: self.cursor,
+            #[cfg(feature = "zeroize")]
+            wipe: self.wipe,
+            #[cfg(feature = "stats")]
+            peak: self.size,
+            journal: Journal::new(),
+        };
+        if let Some(ptr) = self.d_buf{
+            handle_reserve(temp_buf.allocate(self.capacity - self.inline)); // temp_buf will now have memory on the heap
+            let heap = temp_buf.d_buf.unwrap();
+            for i in 0..(self.capacity - self.inline){
+                unsafe {*heap.add(i) = (*ptr.add(i)).clone()};
             }
         }
-        temp_buf.default = self.default.clone();
+        temp_buf.size = self.size;
         temp_buf
     }
 }
@@ -362,7 +1043,48 @@ impl Token {
 /// - The first element in the macro requires the data that will be used in the SmartBuffer
 /// - The second element is the size of the stack portion of the SmartBuffer, whose size must be known on compile time. (CONSTANT)
 /// - The third element is the total required size of the SmartBuffer, which allocates memory if necessary on the heap at runtime!
+///
+/// It also has forms like `vec!`, where the size of the stack portion comes last:
+///
+/// - `buf![a, b, c; N]` holds the elements, the first one is also the default value
+/// - `buf![value; len; N]` holds `len` clones of `value`
+/// - `buf![; N]` is empty, with `T::default()` as the default value
+///
+/// ```
+/// use smart_buffer::buf;
+///
+/// let primes = buf![2u32, 3, 5, 7; 4];
+/// assert_eq!(primes.len(), 4);
+///
+/// let zeros = buf![0u8; 3; 8];
+/// assert_eq!(zeros.len(), 3);
+///
+/// let empty = buf![; 8];
+/// assert_eq!(empty.capacity(), 8);
+/// # let _: &smart_buffer::SmartBuffer<u8, 8> = &empty;
+/// ```
+///
+/// `N` has to be a constant:
+///
+/// ```compile_fail,E0435
+/// use smart_buffer::buf;
+///
+/// let n = 4;
+/// let primes = buf![2u32, 3, 5, 7; n];
+/// ```
 macro_rules! buf {
+    (; $s_len:expr) => {{
+        const __N: $crate::__core::primitive::usize = $s_len;
+        <$crate::SmartBuffer<_,__N> as $crate::__core::default::Default>::default()
+    }};
+    ($data:expr; $len:expr; $s_len:expr) => {{
+        const __N: $crate::__core::primitive::usize = $s_len;
+        $crate::SmartBuffer::<_,__N>::repeat($data, $len)
+    }};
+    ($($elem:expr),+ $(,)?; $s_len:expr) => {{
+        const __N: $crate::__core::primitive::usize = $s_len;
+        $crate::SmartBuffer::<_,__N>::__from_list([$($elem),+])
+    }};
     ($data:expr, $s_len:expr, $t_len:expr) => {
         $crate::SmartBuffer::<_,$s_len>::from_arr($crate::array!(_ => $data; $s_len), $t_len, false)
     }
@@ -395,6 +1117,7 @@ macro_rules! array {
         struct __ArrayVecInner<T> {
             arr: [$crate::__core::mem::MaybeUninit<T>; __COUNT],
             len: $crate::__core::primitive::usize,
+            #[allow(dead_code)]
             token: $crate::Token,
         }
 
@@ -467,5 +1190,1291 @@ macro_rules! array {
     }};
 }
 
+#[macro_use]
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use crate::SmartBuffer;
+    use alloc::string::String;
+    use crate::error::{ReadError, TryReserveError};
+    use crate::spill::Spill;
+    use alloc::vec::Vec;
+
+    #[test]
+    #[allow(unused_mut, unused_variables)]
+    fn it_works() {
+        let mut buf = buf!(0u32, 5, 10);
+        buf.insert_arr(&[4,9,3,2,1,9,3,2,10,19]);
+        //buf.map(|x| x*2);
+        let mut buf_clone = buf.clone();
+        //println!("{:?}", &buf);
+        //println!("{:?}", &buf);
+        //println!("{:?}", &buf_clone);
+
+        let test = SmartBuffer::from_arr([1u8,4,5,6,7], 5, true);
+        //println!("{:?}", &test);
+    }
+
+    #[test]
+    fn clone_and_from_arr_keep_the_size() {
+        let mut buf = buf!(0u32, 5, 10);
+        buf.insert_arr(&[4,9,3,2,1,9,3,2,10,19]);
+        let buf_clone = buf.clone();
+        assert_eq!(buf_clone.get_size(), buf.get_size());
+
+        let test = SmartBuffer::from_arr([1u8,4,5,6,7], 5, true);
+        assert_eq!(test.get_size(), 5);
+    }
+
+    #[test]
+    fn reserve_and_shrink() {
+        let mut buf = SmartBuffer::<u32, 4>::new(0, 4);
+        buf.insert_arr(&[1,2,3,4]);
+        assert_eq!(buf.capacity(), 4);
+        assert_eq!(buf.spare_capacity(), 0);
+
+        buf.reserve(3);
+        assert!(buf.capacity() >= 7);
+        buf.insert_arr(&[5,6,7]);
+        buf.reserve_exact(10);
+        assert_eq!(buf.capacity(), 17);
+        assert_eq!(buf.get_size(), 7);
+        for i in 0..7{
+            assert_eq!(buf[i], i as u32 + 1);
+        }
+
+        buf.shrink_to(10);
+        assert_eq!(buf.capacity(), 10);
+        buf.shrink_to(0);
+        assert_eq!(buf.capacity(), 7);
+
+        buf.reserve(1);
+        buf.set_size(3);
+        buf.shrink_to_fit();
+        assert_eq!(buf.capacity(), 4);
+        assert_eq!(buf[2], 3);
+    }
+
+    #[test]
+    fn try_reserve_overflow() {
+        let mut buf = SmartBuffer::<u64, 2>::new(0, 2);
+        assert_eq!(buf.try_reserve(usize::MAX), Err(TryReserveError::CapacityOverflow));
+        assert_eq!(buf.try_reserve_exact(usize::MAX / 2), Err(TryReserveError::CapacityOverflow));
+        assert_eq!(buf.capacity(), 2);
+    }
+
+    #[test]
+    fn heap_values_are_dropped_and_cloned() {
+        let mut buf = buf!(String::new(), 2, 3);
+        buf.push(String::from("stack"));
+        buf.push(String::from("stack"));
+        buf.push(String::from("heap"));
+        buf.reserve(8);
+        buf.push(String::from("grown"));
+
+        let clone = buf.clone();
+        drop(buf);
+        assert_eq!(clone[2], "heap");
+        assert_eq!(clone[3], "grown");
+        assert_eq!(clone.get_size(), 4);
+    }
+
+    #[test]
+    fn spill_all_stays_contiguous() {
+        let mut buf = SmartBuffer::<u32, 4>::new(0, 4).with_spill(Spill::All {unspill_below: None});
+        buf.insert_arr(&[1,2,3]);
+        assert_eq!(buf.as_slice(), Some(&[1,2,3][..]));
+
+        buf.reserve(4);
+        buf.insert_arr(&[4,5,6]);
+        assert_eq!(buf.as_slice(), Some(&[1,2,3,4,5,6][..]));
+        assert_eq!(buf.as_slices().0.len(), 0);
+
+        buf.set_size(2);
+        buf.shrink_to_fit();
+        assert_eq!(buf.capacity(), 4);
+        assert_eq!(buf.as_slices(), (&[1,2][..], &[][..]));
+    }
+
+    #[test]
+    fn spill_strategies_convert() {
+        let mut buf = SmartBuffer::<u32, 3>::new(0, 8);
+        buf.insert_arr(&[1,2,3,4,5]);
+        assert_eq!(buf.as_slice(), None);
+        assert_eq!(buf.as_slices(), (&[1,2,3][..], &[4,5][..]));
+
+        let buf = buf.with_spill(Spill::All {unspill_below: Some(3)});
+        assert_eq!(buf.as_slice(), Some(&[1,2,3,4,5][..]));
+        assert_eq!(buf.capacity(), 8);
+
+        let mut buf = buf.with_spill(Spill::Split);
+        assert_eq!(buf.as_slices(), (&[1,2,3][..], &[4,5][..]));
+        assert_eq!(buf.capacity(), 11);
+
+        buf = buf.with_spill(Spill::All {unspill_below: Some(3)});
+        buf.set_size(2);
+        assert_eq!(buf.capacity(), 3);
+        assert_eq!(buf.as_slices(), (&[1,2][..], &[][..]));
+    }
+
+    #[test]
+    fn shift_across_the_heap() {
+        for spill in [Spill::Split, Spill::All {unspill_below: None}].iter(){
+            let mut buf = SmartBuffer::<u32, 2>::new(0, 6).with_spill(*spill);
+            buf.insert_arr(&[1,2,3,4,5,6]);
+            buf.shl(3);
+            assert_eq!(buf.as_slices().0.iter().chain(buf.as_slices().1).copied().collect::<Vec<_>>(), [4,5,6,0,0,0]);
+            buf.shr(4);
+            assert_eq!(buf.as_slices().0.iter().chain(buf.as_slices().1).copied().collect::<Vec<_>>(), [0,0,0,0,4,5]);
+            buf.shl(10);
+            assert_eq!((&buf).into_iter().copied().collect::<Vec<_>>(), [0; 6]);
+        }
+    }
+
+    #[test]
+    fn truncate_resets_slots() {
+        let mut buf = buf!(String::new(), 2, 4);
+        buf.insert_slice(&[String::from("a"), String::from("b"), String::from("c")]);
+        buf.truncate(1);
+        assert_eq!(buf.get_size(), 1);
+        assert_eq!(buf[0], "a");
+        assert_eq!(buf.get(2).map(String::as_str), Some(""));
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn secure_buffer_redacts_and_grows() {
+        use crate::secure::SecureBuffer;
+        use alloc::format;
+
+        let mut key = SecureBuffer::<u8, 4>::new(0, 4);
+        key.insert_slice(&[1,2,3,4]);
+        key.reserve(4);
+        key.push(5);
+        assert_eq!(key.as_slices(), (&[1,2,3,4][..], &[5][..]));
+        assert_eq!(format!("{:?}", key), "SecureBuffer { size: 5, .. }");
+
+        assert_eq!(key.iter().copied().collect::<Vec<_>>(), [1, 2, 3, 4, 5]);
+
+        key.truncate(2);
+        assert_eq!((key.len(), key.get(1)), (2, Some(&2)));
+        key[1] = 9;
+        assert_eq!(key[1], 9);
+        key.clear();
+        assert!(key.is_empty());
+        assert_eq!(key.capacity(), 8);
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn secure_insert_wipes_the_overwritten_value() {
+        use crate::secure::SecureBuffer;
+        use alloc::rc::Rc;
+        use core::cell::Cell;
+        use zeroize::Zeroize;
+
+        /// Counts the values dropped without being wiped.
+        #[derive(Clone)]
+        struct Secret(Vec<u8>, Rc<Cell<usize>>);
+
+        impl Zeroize for Secret {
+            fn zeroize(&mut self) {
+                self.0.zeroize();
+            }
+        }
+
+        impl Drop for Secret {
+            fn drop(&mut self) {
+                if self.0.iter().any(|&byte| byte != 0){
+                    self.1.set(self.1.get() + 1);
+                }
+            }
+        }
+
+        let leaked = Rc::new(Cell::new(0));
+        let secret = |bytes: &[u8]| Secret(bytes.to_vec(), leaked.clone());
+        let mut keys = SecureBuffer::from(SmartBuffer::<Secret, 2>::new_in(secret(&[]), 4, Default::default()));
+        keys.insert(secret(b"old"), 0);
+        keys.insert(secret(b"heap"), 3);
+        keys.insert(secret(b"new"), 0);
+        keys.insert(secret(b"newer"), 3);
+        assert_eq!(leaked.get(), 0);
+        assert_eq!(keys[0].0, b"new");
+        drop(keys);
+        assert_eq!(leaked.get(), 0);
+    }
+
+    #[test]
+    fn sort_across_the_seam() {
+        let mut buf = SmartBuffer::<i32, 4>::new(0, 10);
+        buf.insert_arr(&[9, -1, 7, 3, 3, 8, 0, 5, -4, 2]);
+        buf.sort();
+        assert_eq!(buf.iter().copied().collect::<Vec<_>>(), [-4, -1, 0, 2, 3, 3, 5, 7, 8, 9]);
+        assert_eq!(buf.binary_search(&5), Ok(6));
+        assert_eq!(buf.binary_search(&4), Err(6));
+        assert!(buf.contains(&-4));
+        assert_eq!(buf.position(|x| *x > 6), Some(7));
+
+        buf.reverse();
+        assert_eq!(buf.iter().copied().collect::<Vec<_>>(), [9, 8, 7, 5, 3, 3, 2, 0, -1, -4]);
+        buf.sort_unstable_by_key(|x| x.abs());
+        assert_eq!(buf.iter().map(|x| x.abs()).collect::<Vec<_>>(), [0, 1, 2, 3, 3, 4, 5, 7, 8, 9]);
+    }
+
+    #[test]
+    fn stable_sort_keeps_order_of_equals() {
+        let mut buf = buf!((0u8, 0usize), 3, 12);
+        let keys = [3u8, 1, 2, 1, 3, 2, 1, 3, 2, 1, 2, 3];
+        for (i, key) in keys.iter().enumerate(){
+            buf.push((*key, i));
+        }
+        buf.sort_by_key(|pair| pair.0);
+        let mut expected: Vec<_> = keys.iter().copied().zip(0..).collect();
+        expected.sort_by_key(|pair| pair.0);
+        assert_eq!(buf.iter().copied().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn dedup_across_the_seam() {
+        let mut buf = buf!(String::new(), 2, 8);
+        for word in ["a", "a", "b", "b", "b", "c", "a", "a"].iter(){
+            buf.push(String::from(*word));
+        }
+        buf.dedup();
+        assert_eq!(buf.iter().map(String::as_str).collect::<Vec<_>>(), ["a", "b", "c", "a"]);
+        buf.dedup_by_key(|s| s.len());
+        assert_eq!(buf.len(), 1);
+    }
+
+    #[test]
+    fn map_into_keeps_the_layout() {
+        for spill in [Spill::Split, Spill::All {unspill_below: None}].iter(){
+            let mut buf = SmartBuffer::<i16, 3>::new(-1, 6).with_spill(*spill);
+            buf.insert_arr(&[1, 2, 3, 4, 5]);
+            let floats = buf.clone().map_into(-1.0, f32::from);
+            assert_eq!(floats.iter().copied().collect::<Vec<_>>(), [1.0, 2.0, 3.0, 4.0, 5.0]);
+            assert_eq!(floats.capacity(), buf.capacity());
+            assert_eq!(floats.spill(), *spill);
+            assert_eq!(floats.get(5), Some(&-1.0));
+
+            let strings = buf.map_into(String::new(), |x| String::from(if x > 2 {"big"} else {"small"}));
+            assert_eq!(strings.as_slices().0.len() + strings.as_slices().1.len(), 5);
+            assert_eq!(strings[4], "big");
+        }
+    }
+
+    #[test]
+    fn insert_and_remove_keep_the_size() {
+        let mut buf = SmartBuffer::<u8, 2>::new(0, 4);
+        buf.insert(7, 0);
+        assert_eq!(buf.len(), 1);
+        buf.insert(9, 3); // past the size, on the heap
+        assert_eq!(buf.len(), 4);
+        assert_eq!(buf.iter().copied().collect::<Vec<_>>(), [7, 0, 0, 9]);
+        buf.insert(5, 1); // within the size, overwrites
+        assert_eq!(buf.len(), 4);
+
+        assert_eq!(buf.remove(0), Some(7));
+        assert_eq!(buf.iter().copied().collect::<Vec<_>>(), [5, 0, 9]);
+        assert_eq!(buf.remove(3), None);
+        assert_eq!(buf.as_slices(), (&[5, 0][..], &[9][..]));
+    }
+
+    #[test]
+    fn try_map_drops_everything_on_error() {
+        use alloc::rc::Rc;
+
+        let counter = Rc::new(());
+        let mut buf = buf!(Rc::clone(&counter), 2, 6);
+        for _ in 0..5{
+            buf.push(Rc::clone(&counter));
+        }
+        let mut seen = 0;
+        let result = buf.try_map(Rc::clone(&counter), |rc| {
+            seen += 1;
+            if seen == 5 { Err("stop") } else { Ok(rc) }
+        });
+        assert_eq!(result.err(), Some("stop"));
+        assert_eq!(Rc::strong_count(&counter), 1);
+
+        let mut strings = buf!(String::new(), 2, 4);
+        strings.insert_slice(&[String::from("1"), String::from("22"), String::from("x")]);
+        assert!(strings.clone().try_map(0, |s| s.parse::<u8>()).is_err());
+        strings.truncate(2);
+        let mut calls = 0;
+        let parsed = strings.try_map(0, |s| {
+            calls += 1;
+            s.parse::<u8>()
+        }).unwrap();
+        assert_eq!(calls, 2);
+        assert_eq!(parsed.iter().copied().collect::<Vec<_>>(), [1, 22]);
+        assert_eq!((parsed.len(), parsed.capacity()), (2, 4));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_iteration() {
+        use rayon::prelude::*;
+
+        let mut buf: SmartBuffer<u64, 16> = (0..1000u64).into_par_iter().collect();
+        assert_eq!(buf.len(), 1000);
+        assert!(buf.iter().copied().eq(0..1000));
+
+        buf.par_iter_mut().for_each(|x| *x *= 2);
+        assert_eq!(buf.par_iter().sum::<u64>(), 999 * 1000);
+
+        buf.par_extend(Vec::from([1u64, 2, 3]));
+        assert_eq!(buf.len(), 1003);
+
+        let words = buf!(String::new(), 4, 10);
+        let mut words = words.with_spill(Spill::All {unspill_below: None});
+        for i in 0..7u8{
+            words.push(alloc::string::ToString::to_string(&i));
+        }
+        let lengths: Vec<usize> = words.clone().into_par_iter().map(|s| s.len()).collect();
+        assert_eq!(lengths, [1; 7]);
+        let mut split = buf!(String::new(), 4, 10);
+        split.insert_slice(&[String::from("a"), String::from("b"), String::from("c"), String::from("d"), String::from("e")]);
+        assert_eq!(split.into_par_iter().collect::<Vec<_>>().concat(), "abcde");
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn arbitrary_buffers_stay_consistent() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let bytes: Vec<u8> = (0..=255u8).cycle().take(4096).collect();
+        let mut spilled = false;
+        for start in 0..64{
+            let mut u = Unstructured::new(&bytes[start * 7..]);
+            let buf = SmartBuffer::<u8, 4>::arbitrary(&mut u).unwrap();
+            assert!(buf.len() <= buf.capacity());
+            assert_eq!(buf.iter().count(), buf.len());
+            assert!(buf.clone().iter().eq(buf.iter()));
+            spilled |= buf.is_spilled();
+        }
+        assert!(spilled);
+
+        // odd bytes keep the element iterator going until the input runs out
+        let buf = SmartBuffer::<u16, 2>::arbitrary_take_rest(Unstructured::new(&[1; 64])).unwrap();
+        assert_eq!(buf.spill(), Spill::All {unspill_below: Some(0x0101_0101_0101_0101)});
+        assert_eq!(buf[0], 0x0101);
+        assert!(buf.len() > 2);
+    }
+
+    #[cfg(feature = "proptest")]
+    #[test]
+    fn proptest_lengths_hit_the_boundaries() {
+        use proptest::prelude::*;
+        use proptest::strategy::ValueTree;
+        use proptest::test_runner::TestRunner;
+
+        let mut runner = TestRunner::deterministic();
+        let mut seen = [false; 4];
+        for _ in 0..200{
+            let buf = crate::prop::buffer::<_, _, 4>(any::<u8>()).new_tree(&mut runner).unwrap().current();
+            assert_eq!(buf.iter().count(), buf.len());
+            match buf.len(){
+                0 => seen[0] = true,
+                4 => seen[1] = true,
+                5 => seen[2] = true,
+                len if len > 5 => seen[3] = true,
+                _ => {}
+            }
+        }
+        assert_eq!(seen, [true; 4]);
+
+        let buf = any::<SmartBuffer<String, 2>>().new_tree(&mut runner).unwrap().current();
+        assert!(buf.len() <= buf.capacity());
+    }
+
+    #[test]
+    fn matrix_transpose_and_order() {
+        use crate::matrix::{Order, SmartMatrix};
+
+        let mut buf = SmartBuffer::<u32, 4>::new(0, 12);
+        buf.insert_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+        let mut m = SmartMatrix::from_buffer(buf, 3, 4, Order::RowMajor);
+        assert!(m.row(1).eq(&[5, 6, 7, 8]));
+        assert!(m.col(2).eq(&[3, 7, 11]));
+        assert!(m.col(3).rev().eq(&[12, 8, 4]));
+        assert_eq!(m.get(3, 0), None);
+
+        let mut m2 = m.clone().with_order(Order::ColumnMajor);
+        assert!(m2.as_buffer().iter().eq(&[1, 5, 9, 2, 6, 10, 3, 7, 11, 4, 8, 12]));
+        for i in 0..3{
+            for j in 0..4{
+                assert_eq!(m[(i, j)], m2[(i, j)]);
+            }
+        }
+
+        m.transpose();
+        m2.transpose();
+        assert_eq!((m.rows(), m.cols()), (4, 3));
+        assert!(m.row(2).eq(&[3, 7, 11]));
+        assert!(m.as_buffer().iter().eq(&[1, 5, 9, 2, 6, 10, 3, 7, 11, 4, 8, 12]));
+        assert_eq!(m2.order(), Order::ColumnMajor);
+        assert!(m2.as_buffer().iter().eq(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]));
+        assert_eq!(alloc::format!("{:?}", m2), "[[1, 5, 9], [2, 6, 10], [3, 7, 11], [4, 8, 12]]");
+
+        let mut square = SmartMatrix::<String, 9>::new(String::new(), 3, 3);
+        square[(0, 1)] = String::from("up");
+        square.transpose();
+        assert_eq!(square[(1, 0)], "up");
+        assert!(!square.as_buffer().is_spilled());
+    }
+
+    #[test]
+    fn codec_round_trips_across_the_seam() {
+        let mut buf = SmartBuffer::<u8, 5>::new(0, 5);
+        buf.put_u8(0xab);
+        buf.put_u16_le(0x1234);
+        buf.put_u32_be(0xdead_beef);
+        buf.put_i64_le(-2);
+        buf.put_f64_le(-1.5);
+        buf.put_f32_be(core::f32::consts::PI);
+        buf.put_i128_be(i128::MIN);
+        let unsigned = [0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX];
+        let signed = [0, 63, 64, -64, -65, i64::MAX, i64::MIN];
+        for &x in &unsigned{
+            buf.put_uvarint(x);
+        }
+        for &x in &signed{
+            buf.put_ivarint(x);
+        }
+        assert_eq!(&buf.as_slices().0[..5], &[0xab, 0x34, 0x12, 0xde, 0xad]);
+
+        assert_eq!(buf.get_u8(), Ok(0xab));
+        assert_eq!(buf.get_u16_le(), Ok(0x1234));
+        assert_eq!(buf.get_u32_be(), Ok(0xdead_beef));
+        assert_eq!(buf.get_i64_le(), Ok(-2));
+        assert_eq!(buf.get_f64_le(), Ok(-1.5));
+        assert_eq!(buf.get_f32_be(), Ok(core::f32::consts::PI));
+        assert_eq!(buf.get_i128_be(), Ok(i128::MIN));
+        for &x in &unsigned{
+            assert_eq!(buf.get_uvarint(), Ok(x));
+        }
+        for &x in &signed{
+            assert_eq!(buf.get_ivarint(), Ok(x));
+        }
+        assert_eq!(buf.remaining(), 0);
+        assert_eq!(buf.get_u8(), Err(ReadError::UnexpectedEnd {needed: 1, available: 0}));
+
+        buf.set_cursor(1);
+        assert_eq!(buf.get_u16_be(), Ok(0x3412));
+    }
+
+    #[test]
+    fn codec_short_reads_keep_the_cursor() {
+        let mut buf = SmartBuffer::<u8, 4>::new(0, 4);
+        buf.put_slice(&[1, 2, 3]);
+        assert_eq!(buf.get_u32_le(), Err(ReadError::UnexpectedEnd {needed: 4, available: 3}));
+        assert_eq!(buf.cursor(), 0);
+
+        let mut buf = SmartBuffer::<u8, 4>::new(0, 4);
+        buf.put_slice(&[0x80, 0x80]);
+        assert_eq!(buf.get_uvarint(), Err(ReadError::UnexpectedEnd {needed: 3, available: 2}));
+        assert_eq!(buf.cursor(), 0);
+
+        let mut buf = SmartBuffer::<u8, 4>::new(0, 4);
+        buf.put_slice(&[0xff; 9]);
+        buf.put_u8(0x02);
+        assert_eq!(buf.get_uvarint(), Err(ReadError::VarintOverflow));
+        assert_eq!(buf.get_ivarint(), Err(ReadError::VarintOverflow));
+        assert_eq!(buf.cursor(), 0);
+    }
+
+    #[test]
+    fn codec_cursor_follows_a_shrinking_size() {
+        let mut buf = SmartBuffer::<u8, 4>::new(0, 4);
+        buf.put_slice(b"abcdefgh");
+        assert_eq!(buf.get_u64_le(), Ok(u64::from_le_bytes(*b"abcdefgh")));
+        buf.truncate(2);
+        assert_eq!(buf.cursor(), 2);
+        assert_eq!(buf.copy_to_slice(&mut []), Ok(()));
+        assert_eq!(buf.get_u8(), Err(ReadError::UnexpectedEnd {needed: 1, available: 0}));
+        assert_eq!(buf.get_uvarint(), Err(ReadError::UnexpectedEnd {needed: 1, available: 0}));
+
+        buf.put_slice(b"xyz");
+        buf.set_cursor(5);
+        assert_eq!(buf.remove(0), Some(b'a'));
+        assert_eq!(buf.cursor(), 4);
+        buf.set_size(1);
+        assert_eq!(buf.cursor(), 1);
+        buf.clear();
+        assert_eq!((buf.cursor(), buf.get_u8()), (0, Err(ReadError::UnexpectedEnd {needed: 1, available: 0})));
+    }
+
+    #[test]
+    fn sentinel_lengths_and_c_strings() {
+        use alloc::ffi::CString;
+        use core::fmt::Write;
+
+        // a C API filled the whole buffer, past the size and across the seam
+        let mut buf = SmartBuffer::<u8, 4>::new(0, 8);
+        for (i, &byte) in b"serial\0".iter().enumerate(){
+            buf[i] = byte;
+        }
+        assert_eq!(buf.len_until(&0), Some(6));
+        assert_eq!(buf.set_len_from_sentinel(&0), Some(6));
+        assert_eq!(buf.len(), 6);
+        assert_eq!(buf.as_c_str(), None); // not contiguous
+        assert_eq!(buf.to_c_string(), CString::new("serial").unwrap());
+        assert_eq!(buf.set_len_from_sentinel(&b'!'), None);
+        assert_eq!(buf.len(), 6);
+
+        let mut all = SmartBuffer::<u8, 4>::new(0, 8).with_spill(Spill::All {unspill_below: Some(3)});
+        let mut w = all.with_nul_terminator();
+        write!(w, "tty{}", 12).unwrap();
+        assert_eq!(w.as_c_str().unwrap().to_bytes(), b"tty12");
+        w.truncate(2);
+        assert_eq!(w.as_c_str().unwrap().to_bytes(), b"tt");
+        assert_eq!(all.len(), 2);
+
+        let c = CString::new("abcdefgh").unwrap();
+        let from_c = SmartBuffer::<u8, 2>::from(c.as_c_str());
+        assert_eq!(from_c.len(), 8);
+        assert_eq!(from_c.to_c_string(), c);
+        assert_eq!(from_c.len_until(&0), Some(8));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn calc_size_counts_up_to_the_default() {
+        let mut buf = SmartBuffer::<u8, 4>::new(0, 4);
+        buf.insert_slice(&[1, 2, 3, 4]);
+        buf[2] = 0;
+        buf.calc_size();
+        assert_eq!(buf.len(), 2);
+        buf[2] = 3;
+        buf.calc_size();
+        assert_eq!(buf.len(), 4);
+    }
+
+    #[test]
+    fn introspection_follows_the_layout() {
+        use crate::Region;
+
+        let mut buf = SmartBuffer::<u32, 4>::new(0, 4);
+        buf.insert_arr(&[1, 2, 3]);
+        assert!(!buf.is_spilled());
+        assert_eq!((buf.inline_len(), buf.heap_len(), buf.heap_capacity(), buf.heap_bytes()), (3, 0, 0, 0));
+        assert_eq!(buf.total_bytes(), core::mem::size_of::<SmartBuffer<u32, 4>>());
+
+        buf.reserve_exact(3);
+        buf.insert_arr(&[4, 5, 6]);
+        assert!(buf.is_spilled());
+        assert_eq!((buf.inline_len(), buf.heap_len(), buf.heap_capacity(), buf.heap_bytes()), (4, 2, 2, 8));
+        assert_eq!(buf.region_of(3), Some(Region::Stack));
+        assert_eq!(buf.region_of(5), Some(Region::Heap));
+        assert_eq!(buf.region_of(6), None);
+
+        let buf = buf.with_spill(Spill::All {unspill_below: None});
+        assert_eq!((buf.inline_len(), buf.heap_len(), buf.inline_capacity()), (0, 6, 4));
+        assert_eq!(buf.region_of(0), Some(Region::Heap));
+        assert_eq!(buf.heap_bytes(), 4 * buf.heap_capacity());
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats_record_spills_and_peaks() {
+        use crate::stats::{self, AllocEvent, DropEvent, SpillObserver};
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Clone, PartialEq, Debug)]
+        struct Reading(u32);
+
+        struct Spills(AtomicUsize);
+        impl SpillObserver for Spills{
+            fn on_allocate(&self, _: &AllocEvent){}
+
+            fn on_drop(&self, event: &DropEvent){
+                if event.type_name.ends_with("Reading") && event.spilled(){
+                    self.0.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+        static SPILLS: Spills = Spills(AtomicUsize::new(0));
+        stats::set_observer(&SPILLS).unwrap();
+        assert!(stats::set_observer(&SPILLS).is_err());
+
+        let small = buf!(Reading(0), 4, 4);
+        let mut big = buf!(Reading(0), 4, 4);
+        for i in 0..20{
+            big.reserve(1);
+            big.push(Reading(i));
+        }
+        big.truncate(2);
+        drop(small);
+        drop(big);
+
+        let recorded = stats::stats_for::<Reading, 4>().unwrap();
+        assert_eq!(recorded.inline_capacity, 4);
+        assert_eq!(recorded.spills, 1);
+        assert_eq!(recorded.allocations, 3); // 8, 16 then 32 slots
+        assert_eq!(recorded.heap_bytes_allocated, (4 + 12 + 28) * 4);
+        assert_eq!(recorded.buffers(), 2);
+        assert_eq!((recorded.peak_len[0], recorded.peak_len[5]), (1, 1));
+        assert_eq!(SPILLS.0.load(Ordering::Relaxed), 1);
+        assert!(stats::all_stats().any(|s| s.type_name == recorded.type_name));
+        assert!(stats::stats_for::<Reading, 5>().is_none());
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats_count_spills_past_n_only() {
+        use crate::stats;
+
+        #[derive(Clone, Copy, PartialEq, Debug)]
+        struct Sample(u8);
+
+        let mut reserved = SmartBuffer::<Sample, 4>::new(Sample(0), 64);
+        reserved.insert_slice(&[Sample(1), Sample(2), Sample(3), Sample(4)]);
+        drop(reserved);
+        let recorded = stats::stats_for::<Sample, 4>().unwrap();
+        assert_eq!((recorded.spills, recorded.allocations), (0, 1));
+
+        let mut grown = SmartBuffer::<Sample, 4>::new(Sample(0), 64);
+        grown.insert_slice(&[Sample(1), Sample(2), Sample(3), Sample(4), Sample(5)]);
+        grown.clear();
+        drop(grown);
+        let recorded = stats::stats_for::<Sample, 4>().unwrap();
+        assert_eq!((recorded.spills, recorded.allocations), (1, 2));
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats_count_consumed_buffers_as_dropped() {
+        use crate::stats;
+
+        #[derive(Clone, PartialEq, Debug)]
+        struct Raw(u8);
+        #[derive(Clone, PartialEq, Debug)]
+        struct Cooked(u8);
+
+        let mut raw = SmartBuffer::<Raw, 2>::new_in(Raw(0), 4, Default::default());
+        raw.insert_slice(&[Raw(1), Raw(2), Raw(3)]);
+        let cooked = raw.map_into(Cooked(0), |Raw(x)| Cooked(x * 2));
+        assert_eq!(stats::stats_for::<Raw, 2>().unwrap().buffers(), 1);
+        let failed = cooked.try_map(0, |_| Err::<u8, ()>(()));
+        assert!(failed.is_err());
+        let recorded = stats::stats_for::<Cooked, 2>().unwrap();
+        assert_eq!((recorded.buffers(), recorded.peak_len[2]), (1, 1));
+
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::prelude::*;
+
+            #[derive(Clone, PartialEq, Debug)]
+            struct Shared(u8);
+
+            let mut shared = SmartBuffer::<Shared, 2>::new_in(Shared(0), 4, Default::default());
+            shared.insert_slice(&[Shared(1), Shared(2), Shared(3)]);
+            assert_eq!(shared.into_par_iter().count(), 3);
+            assert_eq!(stats::stats_for::<Shared, 2>().unwrap().buffers(), 1);
+        }
+    }
+
+    #[cfg(feature = "checkpoint")]
+    #[test]
+    fn checkpoints_restore_overwritten_values() {
+        let mut buf = SmartBuffer::<String, 2>::default();
+        buf.reserve(4);
+        buf.insert_slice(&[String::from("a"), String::from("b"), String::from("c")]);
+
+        let outer = buf.checkpoint();
+        buf.insert(String::from("B"), 1);
+        buf.push(String::from("d"));
+        let inner = buf.checkpoint();
+        assert_eq!(buf.remove(0).as_deref(), Some("a"));
+        buf.truncate(1);
+        buf.clear();
+        buf.rollback(inner);
+        assert_eq!(buf.iter().map(String::as_str).collect::<Vec<_>>(), ["a", "B", "c", "d"]);
+
+        let inner = buf.checkpoint();
+        buf.set_size(0);
+        buf.commit(inner);
+        assert!(buf.is_empty());
+        buf.rollback(outer);
+        assert_eq!(buf.iter().map(String::as_str).collect::<Vec<_>>(), ["a", "b", "c"]);
+        assert_eq!(buf[3], ""); // the pushed slot went back to the default
+
+        // the heap is given back by truncate and has to come back for the rollback
+        let mut all = SmartBuffer::<u8, 2>::new(0, 2).with_spill(Spill::All {unspill_below: Some(2)});
+        all.reserve(4);
+        all.insert_slice(&[1, 2, 3, 4]);
+        {
+            let mut tx = all.transaction();
+            tx.truncate(1);
+            assert!(!tx.is_spilled());
+        }
+        assert_eq!(all.iter().copied().collect::<Vec<_>>(), [1, 2, 3, 4]);
+        let mut tx = all.transaction();
+        tx.reserve(1);
+        tx.push(5);
+        tx.commit();
+        assert_eq!(all.len(), 5);
+    }
+
+    #[cfg(not(feature = "checkpoint"))]
+    #[test]
+    fn journal_is_free_without_checkpoints() {
+        assert_eq!(core::mem::size_of::<crate::checkpoint::Journal<String>>(), 0);
+    }
+
+    #[cfg(feature = "checkpoint")]
+    #[test]
+    fn rollback_undoes_composite_mutators() {
+        type Mutator = fn(&mut SmartBuffer<u8, 3>);
+        let mutators: [(&str, Mutator); 16] = [
+            ("dedup", |buf| buf.dedup()),
+            ("dedup_by_key", |buf| buf.dedup_by_key(|x| *x / 2)),
+            ("reverse", |buf| buf.reverse()),
+            ("sort", |buf| buf.sort()),
+            ("sort_unstable", |buf| buf.sort_unstable()),
+            ("sort_by_key", |buf| buf.sort_by_key(|x| core::cmp::Reverse(*x))),
+            ("shl", |buf| buf.shl(2)),
+            ("shr", |buf| buf.shr(2)),
+            ("map", |buf| buf.map(|x| x + 1)),
+            ("fill", |buf| buf.fill(9)),
+            ("resize", |buf| buf.resize(8, 9)),
+            ("remove", |buf| {buf.remove(1);}),
+            ("extend_from_within", |buf| buf.extend_from_within(..3)),
+            ("split_off", |buf| {buf.split_off(2);}),
+            ("insert_slice_at", |buf| buf.insert_slice_at(&[7, 7], 4)),
+            ("clear", |buf| buf.clear()),
+        ];
+        for (name, mutate) in mutators{
+            let mut buf = SmartBuffer::<u8, 3>::new(0, 8);
+            buf.insert_slice(&[1, 1, 2, 5, 4, 4]);
+            let before = buf.clone();
+            let checkpoint = buf.checkpoint();
+            mutate(&mut buf);
+            buf.rollback(checkpoint);
+            assert_eq!(buf.len(), before.len(), "{}", name);
+            for i in 0..before.capacity(){
+                assert_eq!(buf.get(i), before.get(i), "{} at {}", name, i);
+            }
+        }
+
+        // a partial append compacts what is left in the source
+        let mut region = [core::mem::MaybeUninit::<u8>::uninit(); 1];
+        let mut full = SmartBuffer::<u8, 1, _>::with_overflow(0, &mut region);
+        let mut other = SmartBuffer::<u8, 4>::new(0, 4);
+        other.insert_slice(&[1, 2, 3, 4]);
+        let checkpoint = other.checkpoint();
+        full.append(&mut other);
+        assert_eq!(other.iter().copied().collect::<Vec<_>>(), [3, 4]);
+        other.rollback(checkpoint);
+        assert_eq!(other.iter().copied().collect::<Vec<_>>(), [1, 2, 3, 4]);
+
+        let mut c = SmartBuffer::<u8, 4>::new(0, 4);
+        c.insert_slice(b"ab");
+        let checkpoint = c.checkpoint();
+        c.with_nul_terminator().put_slice(b"c");
+        c.rollback(checkpoint);
+        assert_eq!(c.as_slices().0, b"ab");
+        assert_eq!(c.get(2), Some(&0));
+        assert_eq!(c.get(3), Some(&0));
+    }
+
+    #[test]
+    fn cow_buffers_share_the_heap_until_written() {
+        use crate::cow::{CowBuffer, RcCowBuffer};
+
+        let mut buf = SmartBuffer::<String, 2>::default();
+        buf.reserve_exact(5);
+        for word in ["a", "b", "c", "d"]{
+            buf.push(String::from(word));
+        }
+        let base: RcCowBuffer<String, 2> = CowBuffer::from(buf);
+        assert_eq!((base.len(), base.capacity()), (4, 5));
+
+        let mut copy = base.clone();
+        assert!(copy.shares_heap_with(&base));
+        copy.push(String::from("e"));
+        assert!(!copy.shares_heap_with(&base));
+        copy.push(String::from("f")); // grows the now unique heap in place
+        assert_eq!(copy.capacity(), 10);
+        assert_eq!(base.len(), 4);
+
+        let mut other = base.clone();
+        other.truncate(1);
+        assert!(other.shares_heap_with(&base)); // nothing was written to the heap
+        assert_eq!(other.iter().collect::<Vec<_>>(), ["a"]);
+        other.push(String::from("x"));
+        other.push(String::from("y"));
+        assert!(!other.shares_heap_with(&base));
+        assert_eq!(other.iter().collect::<Vec<_>>(), ["a", "x", "y"]);
+        assert_eq!(base.iter().rev().collect::<Vec<_>>(), ["d", "c", "b", "a"]);
+
+        let (stack, heap) = copy.make_mut();
+        assert_eq!((stack.len(), heap.len()), (2, 4));
+        heap[0].push('!');
+        let back = SmartBuffer::from(copy);
+        assert_eq!(back.capacity(), 10);
+        assert_eq!(back.iter().map(String::as_str).collect::<Vec<_>>(), ["a", "b", "c!", "d", "e", "f"]);
+        assert_eq!(alloc::format!("{:?}", base), r#"["a", "b", "c", "d"]"#);
+    }
+
+    #[test]
+    fn smart_strings_stay_utf8() {
+        use crate::string::SmartString;
+        use core::fmt::Write;
+
+        let mut s = SmartString::<4>::new();
+        s.push_str("ab");
+        s.push('é');
+        assert_eq!(s, "abé");
+        assert_eq!(s.len(), 4);
+        assert!(!s.as_buffer().is_spilled());
+        write!(s, "{}", 42).unwrap();
+        assert!(s.as_buffer().is_spilled());
+        assert_eq!(s.as_str(), "abé42");
+        assert_eq!(s.pop(), Some('2'));
+        assert_eq!(s.pop(), Some('4'));
+        assert_eq!(s.pop(), Some('é'));
+        assert_eq!(String::from(s.clone()), "ab");
+        s.make_ascii_uppercase();
+        assert_eq!("AB", s);
+
+        let parsed: SmartString<8> = "ünïcode".parse().unwrap();
+        assert_eq!(parsed.chars().count(), 7);
+        assert_eq!(parsed, String::from("ünïcode"));
+        let u = SmartString::<2>::from("u");
+        assert!(parsed > u);
+        assert_eq!(alloc::format!("{}|{:?}", parsed, parsed), "ünïcode|\"ünïcode\"");
+
+        let bytes = SmartBuffer::<u8, 2>::from_arr([0xff, 0], 2, true);
+        assert!(SmartString::from_utf8(bytes).is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn smart_string_truncate_checks_boundaries() {
+        let mut s = crate::string::SmartString::<4>::from("é");
+        s.truncate(1);
+    }
+
+    #[test]
+    fn buf_macro_forms() {
+        let words = buf![String::from("a"), String::from("b"), String::from("c"); 2];
+        assert_eq!(words.len(), 3);
+        assert!(words.is_spilled());
+        assert_eq!(words[2], "c");
+
+        let trailing = buf![1u8, 2,; 4];
+        assert_eq!(trailing.len(), 2);
+        assert_eq!(trailing.capacity(), 4);
+
+        let mut ones = buf![1u16; 6; 4];
+        assert_eq!(ones.len(), 6);
+        assert_eq!(ones.iter().sum::<u16>(), 6);
+        ones.truncate(0);
+        assert_eq!(ones.capacity(), 6);
+
+        let empty: SmartBuffer<String, 3> = buf![; 3];
+        assert!(empty.is_empty());
+        assert!(!empty.is_spilled());
+
+        let old = buf!(0u8, 2, 5);
+        assert_eq!((old.len(), old.capacity()), (0, 5));
+    }
+
+    #[test]
+    fn fill_and_resize_across_the_seam() {
+        let mut buf = SmartBuffer::<u32, 3>::from_fn(2, |i| i as u32 + 1);
+        assert_eq!(buf.as_slices(), (&[1, 2][..], &[][..]));
+
+        buf.resize(5, 7);
+        assert_eq!(buf.as_slices(), (&[1, 2, 7][..], &[7, 7][..]));
+        let mut next = 10;
+        buf.resize_with(6, || {next += 1; next});
+        assert_eq!(buf[5], 11);
+        buf.resize(1, 0);
+        assert_eq!(buf.len(), 1);
+        assert_eq!(buf.capacity(), 6);
+
+        buf.resize(4, 0);
+        buf.fill(9);
+        assert_eq!(buf.iter().copied().collect::<Vec<_>>(), [9, 9, 9, 9]);
+        let mut i = 0;
+        buf.fill_with(|| {i += 1; i});
+        assert_eq!(buf.iter().copied().collect::<Vec<_>>(), [1, 2, 3, 4]);
+
+        buf.clear();
+        assert!(buf.is_empty());
+        assert_eq!(buf.capacity(), 6);
+        assert_eq!(unsafe {*buf.get_unchecked(3)}, 0);
+
+        let words = SmartBuffer::<String, 2>::repeat(String::from("x"), 4);
+        assert_eq!(words.len(), 4);
+        assert_eq!(words[3], "x");
+        let mut all = SmartBuffer::<u8, 4>::repeat(0, 2).with_spill(Spill::All {unspill_below: None});
+        all.resize(6, 1);
+        assert_eq!(all.as_slices(), (&[][..], &[0, 0, 1, 1, 1, 1][..]));
+    }
+
+    #[test]
+    fn append_and_split_move_elements() {
+        let mut head = buf!(String::new(), 2, 2);
+        head.insert_slice(&[String::from("a"), String::from("b")]);
+        let mut tail = SmartBuffer::<String, 4>::default();
+        tail.insert_slice(&[String::from("c"), String::from("d"), String::from("e")]);
+
+        head.append(&mut tail);
+        assert_eq!(head.len(), 5);
+        assert_eq!(head.capacity(), 5);
+        assert!(tail.is_empty());
+        assert_eq!(tail.capacity(), 4);
+
+        let mut rest = head.split_off(1);
+        assert_eq!(head.iter().map(String::as_str).collect::<Vec<_>>(), ["a"]);
+        assert_eq!(rest.iter().map(String::as_str).collect::<Vec<_>>(), ["b", "c", "d", "e"]);
+        assert_eq!(unsafe {head.get_unchecked(1)}, "");
+        assert!(rest.is_spilled());
+        assert!(head.split_off(1).is_empty());
+
+        rest.extend_from_within(..=1);
+        assert_eq!(rest.iter().map(String::as_str).collect::<Vec<_>>(), ["b", "c", "d", "e", "b", "c"]);
+
+        let mut parts = [head, rest, SmartBuffer::default()];
+        let joined = SmartBuffer::<String, 2>::join(&mut parts, &[String::from("|")], String::new()).unwrap();
+        assert_eq!(joined.iter().map(String::as_str).collect::<String>(), "a|bcdebc|");
+        assert_eq!(joined.capacity(), 9);
+        assert!(parts.iter().all(SmartBuffer::is_empty));
+        let mut none: [SmartBuffer<u8, 2>; 0] = [];
+        assert!(SmartBuffer::<u8, 2>::concat(&mut none, 0).unwrap().is_empty());
+
+        #[derive(Clone, PartialEq, Debug)]
+        struct Unit; // no Default
+        let mut units = [SmartBuffer::<Unit, 1>::from_default(Unit), SmartBuffer::from_default(Unit)];
+        units[0].push(Unit);
+        units[1].push(Unit);
+        // a zero-sized element type allows a separator of any length
+        let huge: &[Unit] = unsafe {core::slice::from_raw_parts(core::ptr::NonNull::dangling().as_ptr(), usize::MAX)};
+        assert_eq!(SmartBuffer::<Unit, 2>::join(&mut units, huge, Unit).err(), Some(TryReserveError::CapacityOverflow));
+        assert!(units.iter().all(|part| part.len() == 1));
+        let mut empty = [SmartBuffer::<Unit, 1>::from_default(Unit), SmartBuffer::from_default(Unit), SmartBuffer::from_default(Unit)];
+        assert_eq!(SmartBuffer::<Unit, 2>::join(&mut empty, huge, Unit).err(), Some(TryReserveError::CapacityOverflow));
+        assert_eq!(SmartBuffer::<Unit, 2>::concat(&mut units, Unit).map(|buf| buf.len()), Ok(2));
+    }
+
+    #[test]
+    fn chunks_and_windows_span_the_seam() {
+        use crate::chunks::Chunk;
+
+        let buf = SmartBuffer::<u8, 4>::from_fn(7, |i| i as u8);
+        let chunks = buf.chunks(3).collect::<Vec<_>>();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].as_slice(), Some(&[0, 1, 2][..]));
+        assert!(matches!(chunks[1], Chunk::Split(&[3], &[4, 5])));
+        assert_eq!(chunks[2].as_slice(), Some(&[6][..]));
+        assert_eq!(buf.chunks(3).rev().map(|c| c.len()).collect::<Vec<_>>(), [1, 3, 3]);
+
+        let mut exact = buf.chunks_exact(2);
+        assert_eq!(exact.len(), 3);
+        assert_eq!(exact.remainder().as_slice(), Some(&[6][..]));
+        assert_eq!(exact.next_back().unwrap().as_slice(), Some(&[4, 5][..]));
+        assert_eq!(exact.nth(1).unwrap().as_slices(), (&[2, 3][..], &[][..]));
+        assert!(exact.next().is_none());
+
+        let rchunks = buf.rchunks(3).collect::<Vec<_>>();
+        assert_eq!(rchunks[0].as_slice(), Some(&[4, 5, 6][..]));
+        assert_eq!(rchunks[1].as_slice(), Some(&[1, 2, 3][..]));
+        assert_eq!(rchunks[2].as_slice(), Some(&[0][..]));
+        assert_eq!(*buf.rchunks(3).next_back().unwrap().as_slice().unwrap(), [0]);
+
+        let windows = buf.windows(3);
+        assert_eq!(windows.len(), 5);
+        let split = windows.clone().filter(|w| w.as_slice().is_none()).count();
+        assert_eq!(split, 2);
+        for (i, window) in windows.enumerate(){
+            assert_eq!(window[2], i as u8 + 2);
+            assert_eq!(window.to_array::<3>(), Some([i as u8, i as u8 + 1, i as u8 + 2]));
+            assert_eq!(window.to_array::<2>(), None);
+            let mut out = [0; 3];
+            window.copy_to_slice(&mut out);
+            assert!(window == out[..]);
+        }
+        assert_eq!(buf.windows(3).next_back().unwrap().as_slice(), Some(&[4, 5, 6][..]));
+        assert_eq!(buf.windows(8).count(), 0);
+        assert_eq!(SmartBuffer::<u8, 4>::default().chunks(2).count(), 0);
+
+        let all = buf.clone().with_spill(Spill::All {unspill_below: None});
+        assert!(all.windows(2).all(|w| w.as_slice().is_some()));
+    }
+
+    #[test]
+    fn custom_storage_backs_the_overflow() {
+        use crate::storage::{Global, Storage};
+        use alloc::rc::Rc;
+        use core::cell::Cell;
+        use core::ptr::NonNull;
+
+        /// The global allocator, counting live regions and capping them at 5 slots.
+        #[derive(Clone, Default)]
+        struct Counting(Rc<Cell<usize>>);
+
+        unsafe impl<T> Storage<T> for Counting {
+            fn allocate(&mut self, slots: usize) -> Result<NonNull<T>, TryReserveError> {
+                self.0.set(self.0.get() + 1);
+                Storage::<T>::allocate(&mut Global, slots)
+            }
+
+            unsafe fn deallocate(&mut self, ptr: NonNull<T>, slots: usize) {
+                self.0.set(self.0.get() - 1);
+                Global.deallocate(ptr, slots)
+            }
+
+            fn max_slots(&self) -> usize {
+                5
+            }
+        }
+
+        let live = Counting::default();
+        let mut buf = SmartBuffer::<u32, 2, Counting>::new_in(0, 4, live.clone());
+        assert_eq!(live.0.get(), 1);
+        buf.insert_slice(&[1, 2, 3, 4]);
+        buf.reserve(1);
+        assert_eq!(buf.capacity(), 7); // doubling would ask for 6 heap slots
+        buf.reserve(5);
+        assert_eq!(buf.capacity(), 9); // more than the cap when the elements need it
+        assert_eq!(live.0.get(), 1);
+
+        let copy = buf.clone();
+        let tail = buf.split_off(1);
+        assert_eq!(live.0.get(), 3);
+        assert_eq!(tail.iter().copied().collect::<Vec<_>>(), [2, 3, 4]);
+        assert_eq!(copy.iter().sum::<u32>(), 10);
+        buf.shrink_to_fit();
+        assert_eq!(live.0.get(), 2);
+        drop((copy, tail));
+        assert_eq!(live.0.get(), 0);
+    }
+
+    #[test]
+    fn overflow_lands_in_caller_memory() {
+        use alloc::rc::Rc;
+        use core::mem::MaybeUninit;
+
+        let mut region = [const { MaybeUninit::<Rc<u8>>::uninit() }; 6];
+        let start = region.as_ptr() as *const Rc<u8>;
+        let shared = Rc::new(7);
+        {
+            let mut buf = SmartBuffer::<Rc<u8>, 2, _>::with_overflow(Rc::new(0), &mut region);
+            buf.reserve(8);
+            for _ in 0..10{
+                buf.push(shared.clone());
+            }
+            assert_eq!(buf.len(), 8);
+            assert_eq!(buf.capacity(), 8);
+            assert_eq!(buf.try_reserve(1), Err(TryReserveError::CapacityOverflow));
+            assert!(core::ptr::eq(buf.as_slices().1.as_ptr(), start));
+            assert_eq!(Rc::strong_count(&shared), 9);
+        }
+        assert_eq!(Rc::strong_count(&shared), 1);
+    }
+
+    #[test]
+    fn with_spill_stays_split_when_the_storage_is_full() {
+        use core::mem::MaybeUninit;
+
+        let mut region = [MaybeUninit::<u8>::uninit(); 4];
+        let mut buf = SmartBuffer::<u8, 2, _>::with_overflow(0, &mut region);
+        buf.resize(5, 1);
+        let buf = buf.with_spill(Spill::All {unspill_below: None}); // would need 5 heap slots
+        assert_eq!(buf.spill(), Spill::Split);
+        assert_eq!(buf.as_slices(), (&[1, 1][..], &[1, 1, 1][..]));
+
+        let mut region = [MaybeUninit::<u8>::uninit(); 8];
+        let mut buf = SmartBuffer::<u8, 2, _>::with_overflow(0, &mut region);
+        buf.resize(5, 1);
+        let buf = buf.with_spill(Spill::All {unspill_below: None});
+        assert_eq!(buf.spill(), Spill::All {unspill_below: None});
+        assert_eq!(buf.as_slice(), Some(&[1, 1, 1, 1, 1][..]));
+    }
+
+    #[test]
+    fn pool_recycles_segments() {
+        use crate::pool::SmartBufferPool;
+
+        let pool = SmartBufferPool::<u32, 4>::new(64);
+        let mut first = pool.get(0);
+        first.reserve_exact(9); // 5 heap slots, a class of 8
+        let segment = first.as_slices().1.as_ptr();
+        assert_eq!(first.capacity(), 9);
+        first.reserve_exact(12); // still fits the class
+        assert_eq!(first.as_slices().1.as_ptr(), segment);
+        drop(first);
+        assert_eq!(pool.retained_bytes(), 32);
+
+        let mut second = pool.get(0);
+        second.resize(10, 3);
+        assert_eq!(second.as_slices().1.as_ptr(), segment);
+        assert_eq!(pool.retained_bytes(), 0);
+
+        let mut third = pool.get(0);
+        third.reserve_exact(20);
+        drop((second, third)); // the class of 16 would go past the cap
+        assert_eq!(pool.retained_bytes(), 32);
+        pool.release();
+        assert_eq!(pool.retained_bytes(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn pool_is_shared_between_threads() {
+        use crate::pool::SmartBufferPool;
+
+        let pool = SmartBufferPool::<u8, 16>::new(1 << 16);
+        std::thread::scope(|scope| {
+            for t in 0..4u8{
+                let pool = pool.clone();
+                scope.spawn(move || {
+                    for _ in 0..100{
+                        let mut buf = pool.get(0);
+                        buf.resize(200, t);
+                        assert!(buf.iter().all(|&b| b == t));
+                    }
+                });
+            }
+        });
+        assert!(pool.retained_bytes() <= 4 * 256);
+        assert!(pool.retained_bytes() > 0);
+    }
+
+    static TABLE: SmartBuffer<u16, 3> = SmartBuffer::from_array_const([10, 20, 30]);
+    const EMPTY: SmartBuffer<u8, 8> = SmartBuffer::new_const(0);
+
+    #[test]
+    fn const_constructors() {
+        assert_eq!(TABLE.len(), 3);
+        assert_eq!(TABLE[2], 30);
+        assert!(!TABLE.is_spilled());
+
+        let mut buf = EMPTY;
+        assert!(buf.is_empty());
+        assert_eq!(buf.capacity(), 8);
+        buf.insert_arr(&[1; 9]);
+        assert_eq!(buf.len(), 8);
+        buf.reserve(1);
+        buf.push(1);
+        assert!(buf.is_spilled());
+
+        let all = EMPTY.with_spill(Spill::All {unspill_below: None});
+        assert!(!all.is_spilled());
+        let mut all = all;
+        all.reserve(9);
+        assert!(all.is_spilled());
+    }
+}
+
+#[cfg(all(test, not(feature = "alloc")))]
+mod inline_tests {
+    use crate::SmartBuffer;
+    use crate::error::TryReserveError;
+
+    #[test]
+    fn stays_inline_without_alloc() {
+        let mut buf = SmartBuffer::<u16, 3>::new(0, 10);
+        assert_eq!(buf.capacity(), 3);
+        buf.insert_arr(&[1,2,3,4]);
+        assert_eq!(buf.get_size(), 3);
+        assert_eq!(buf.try_reserve(1), Err(TryReserveError::CapacityOverflow));
+        assert_eq!((&buf).into_iter().copied().sum::<u16>(), 6);
+    }
+
+    #[test]
+    fn stable_sort_without_alloc() {
+        let mut buf = SmartBuffer::<(u8, u8), 40>::new((0, 0), 40);
+        for i in 0..40u8{
+            buf.push((i % 5 * 7 % 5, i));
+        }
+        buf.sort_by_key(|pair| pair.0);
+        for i in 1..40{
+            let (a, b) = (buf[i - 1], buf[i]);
+            assert!(a.0 < b.0 || (a.0 == b.0 && a.1 < b.1));
+        }
+    }
+
+    #[test]
+    fn smart_string_reports_short_writes() {
+        use crate::string::SmartString;
+        use core::fmt::Write;
+
+        let mut s = SmartString::<4>::new();
+        assert!(write!(s, "hello world").is_err());
+        assert_eq!(s, "");
+        let id = 123;
+        assert!(write!(s, "ab{}", id).is_err());
+        assert_eq!(s, "ab");
+        assert_eq!(s.try_push_str("cde"), Err(TryReserveError::CapacityOverflow));
+        assert_eq!(s.try_push_str("cd"), Ok(()));
+        s.push_str("é"); // push_str still appends what fits
+        assert_eq!(s, "abcd");
+    }
+
+    #[test]
+    fn smart_string_from_str_keeps_what_fits() {
+        use crate::string::SmartString;
+
+        let parsed = "hello world".parse::<SmartString<4>>().unwrap();
+        assert_eq!(parsed, "hell");
+        assert_eq!(SmartString::<4>::from("aé!"), "aé!");
+        assert_eq!(SmartString::<4>::from("abcé"), "abc");
+        let empty = SmartString::<4>::with_capacity(64);
+        assert_eq!((empty.len(), empty.capacity()), (0, 4));
+    }
+
+    #[test]
+    fn try_put_reports_a_full_buffer() {
+        let mut frame = SmartBuffer::<u8, 6>::new(0, 6);
+        assert_eq!(frame.try_put_u32_le(7), Ok(()));
+        assert_eq!(frame.try_put_u32_le(8), Err(TryReserveError::CapacityOverflow));
+        assert_eq!(frame.try_put_uvarint(300), Ok(()));
+        assert_eq!(frame.try_put_ivarint(-1), Err(TryReserveError::CapacityOverflow));
+        assert_eq!(frame.len(), 6);
+        assert_eq!(frame.get_u32_le(), Ok(7));
+        assert_eq!(frame.get_uvarint(), Ok(300));
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity overflow")]
+    fn put_panics_on_a_full_buffer() {
+        let mut frame = SmartBuffer::<u8, 2>::new(0, 2);
+        frame.put_u16_be(1);
+        frame.put_u8(2);
+    }
+
+    #[test]
+    fn nul_terminated_reports_a_full_buffer() {
+        use core::ffi::CStr;
+        use core::fmt::Write;
 
+        let mut path = SmartBuffer::<u8, 4>::new(0xff, 4);
+        let mut w = path.try_with_nul_terminator().unwrap();
+        assert_eq!(w.try_put_slice(b"dev"), Ok(()));
+        assert_eq!(w.try_push(b'x'), Err(TryReserveError::CapacityOverflow));
+        assert!(write!(w, "/").is_err());
+        assert_eq!(w.as_c_str().unwrap().to_bytes(), b"dev");
+        path.push(b'x');
+        assert!(path.try_with_nul_terminator().is_err());
 
+        let long = CStr::from_bytes_with_nul(b"ttyUSB0\0").unwrap();
+        assert!(SmartBuffer::<u8, 8>::try_from_c_str(long).is_ok());
+        assert_eq!(SmartBuffer::<u8, 7>::try_from_c_str(long).err(), Some(TryReserveError::CapacityOverflow));
+    }
+
+    #[test]
+    fn overflow_without_alloc() {
+        use core::mem::MaybeUninit;
+
+        let mut region = [MaybeUninit::<u16>::uninit(); 4];
+        let mut buf = SmartBuffer::<u16, 3, _>::with_overflow(0, &mut region);
+        buf.resize(10, 4);
+        assert_eq!(buf.len(), 7);
+        assert_eq!(buf.heap_len(), 4);
+        assert_eq!(buf.try_reserve(1), Err(TryReserveError::CapacityOverflow));
+    }
+}
@@ -0,0 +1,129 @@
+use crate::SmartBuffer;
+use crate::storage::Global;
+
+use core::alloc::Layout;
+use core::convert::Infallible;
+use core::marker::PhantomData;
+use core::mem::forget;
+use core::ptr;
+
+/// Owns the heap of a SmartBuffer while it is being mapped. Slots below `converted` already hold
+/// a `U` written back in place, slots from `pending` on still hold a `T`, and anything in between
+/// has been moved out. Dropping it cleans up both kinds and frees the memory.
+struct MapGuard<T, U>{
+    ptr: *mut T,
+    len: usize, // 0 if there is no heap
+    converted: usize,
+    pending: usize,
+    wipe: bool,
+    mapped: PhantomData<U>,
+}
+
+impl<T, U> Drop for MapGuard<T, U>{
+    fn drop(&mut self) {
+        if self.len > 0{
+            unsafe {
+                ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.ptr as *mut U, self.converted));
+                ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.ptr.add(self.pending), self.len - self.pending));
+                crate::free(&mut Global, self.ptr, self.len, self.wipe);
+            }
+        }
+    }
+}
+
+impl<T, const N:usize> SmartBuffer<T,N>
+    where T: Clone
+{
+    /// Converts every element with `f`, keeping `N`, the spill strategy and the capacity.
+    ///
+    /// `f` is applied to each element in order, the unused slots are filled with `default`, which
+    /// becomes the new default value. The heap allocation is reused when `U` has the same size and
+    /// alignment as `T`.
+    ///
+    /// ```
+    /// use smart_buffer::SmartBuffer;
+    ///
+    /// let mut samples = SmartBuffer::<i16, 4>::new(0, 4);
+    /// samples.insert_arr(&[-16384, 0, 8192, 16384]);
+    /// let normalized = samples.map_into(0.0, |x| f32::from(x) / 32768.0);
+    /// assert_eq!(normalized[0], -0.5);
+    /// assert_eq!(normalized[3], 0.5);
+    /// ```
+    pub fn map_into<U, F>(self, default: U, mut f: F) -> SmartBuffer<U,N>
+    where U: Clone,
+        F: FnMut(T) -> U
+    {
+        match self.try_map(default, |elem| Ok::<U, Infallible>(f(elem))){
+            Ok(buf) => buf,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Fallible version of [`map_into`](Self::map_into). Stops at the first `Err` and returns it,
+    /// after dropping every element mapped or not mapped so far.
+    ///
+    /// ```
+    /// use smart_buffer::SmartBuffer;
+    ///
+    /// let mut fields = SmartBuffer::<&str, 2>::new("", 4);
+    /// fields.insert_slice(&["7", "42"]);
+    /// let parsed = fields.try_map(0, |s| s.parse::<u8>()).unwrap();
+    /// assert_eq!(parsed.iter().copied().collect::<Vec<_>>(), [7, 42]);
+    /// ```
+    pub fn try_map<U, E, F>(self, default: U, mut f: F) -> Result<SmartBuffer<U,N>, E>
+    where U: Clone,
+        F: FnMut(T) -> Result<U, E>
+    {
+        let parts = self.into_raw_parts();
+        let (inline, size, heap_len) = (parts.inline, parts.size, parts.capacity - parts.inline);
+        let mut stack = IntoIterator::into_iter(parts.s_buf);
+        let mut heap = MapGuard::<T, U>{
+            ptr: parts.d_buf.unwrap_or(ptr::null_mut()),
+            len: heap_len,
+            converted: 0,
+            pending: 0,
+            wipe: parts.wipe,
+            mapped: PhantomData,
+        };
+
+        let mut out = SmartBuffer::<U,N>::from_default(default);
+        out.spill = parts.spill;
+        out.cursor = parts.cursor;
+        #[cfg(feature = "zeroize")]
+        {
+            out.wipe = parts.wipe;
+        }
+
+        for i in 0..size.min(inline){
+            out.s_buf[i] = f(stack.next().unwrap())?;
+        }
+
+        let live = size - size.min(inline);
+        let reuse = heap_len > 0 && Layout::array::<U>(heap_len).ok() == Layout::array::<T>(heap_len).ok();
+        if reuse{
+            for j in 0..heap_len{
+                let value = unsafe {ptr::read(heap.ptr.add(j))};
+                heap.pending = j + 1;
+                let mapped = if j < live { f(value)? } else { drop(value); out.default.clone() };
+                unsafe {(heap.ptr as *mut U).add(j).write(mapped)};
+                heap.converted = j + 1;
+            }
+            out.d_buf = Some(heap.ptr as *mut U);
+            out.capacity = inline + heap_len;
+            out.inline = inline;
+            forget(heap);
+        } else {
+            if heap_len > 0{
+                crate::handle_reserve(out.allocate(heap_len));
+            }
+            for j in 0..live{
+                let value = unsafe {ptr::read(heap.ptr.add(j))};
+                heap.pending = j + 1;
+                unsafe {*out.get_mut_unchecked(inline + j) = f(value)?};
+            }
+        }
+
+        out.size = size;
+        Ok(out)
+    }
+}
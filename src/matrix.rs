@@ -0,0 +1,309 @@
+use crate::SmartBuffer;
+
+use core::fmt::{Debug, Formatter};
+use core::iter::FusedIterator;
+use core::ops::{Index, IndexMut};
+
+/// How the elements of a SmartMatrix are laid out in its SmartBuffer.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Order {
+    /// Each row is stored contiguously. This is the default.
+    #[default]
+    RowMajor,
+    /// Each column is stored contiguously.
+    ColumnMajor,
+}
+
+/// A two-dimensional matrix stored in a SmartBuffer, so small matrices live on the stack and
+/// large ones spill onto the heap.
+///
+/// ```
+/// use smart_buffer::matrix::SmartMatrix;
+///
+/// let mut m = SmartMatrix::<f32, 9>::new(0.0, 3, 3);
+/// for i in 0..3{
+///     m[(i, i)] = 1.0;
+/// }
+/// m[(0, 2)] = 5.0;
+/// m.transpose();
+/// assert_eq!(m[(2, 0)], 5.0);
+/// assert!(m.row(1).eq(&[0.0, 1.0, 0.0]));
+/// ```
+#[derive(Clone)]
+pub struct SmartMatrix<T, const N:usize>
+    where T: Clone
+{
+    buf: SmartBuffer<T,N>,
+    rows: usize,
+    cols: usize,
+    order: Order,
+}
+
+impl<T, const N:usize> SmartMatrix<T,N>
+    where T: Clone
+{
+    /// Creates a `rows` x `cols` row-major matrix where every element is `value`.
+    pub fn new(value: T, rows:usize, cols:usize) -> Self{
+        let len = rows.checked_mul(cols).expect("capacity overflow");
+        let mut buf = SmartBuffer::from_default(value);
+        buf.reserve_exact(len);
+        buf.size = len; // every slot already holds the default value
+        Self{
+            buf,
+            rows,
+            cols,
+            order: Order::RowMajor,
+        }
+    }
+
+    /// Creates a `rows` x `cols` matrix from the elements of a SmartBuffer in the given order.
+    ///
+    /// Panics if the SmartBuffer does not hold exactly `rows * cols` elements.
+    pub fn from_buffer(buf: SmartBuffer<T,N>, rows:usize, cols:usize, order: Order) -> Self{
+        assert_eq!(rows.checked_mul(cols), Some(buf.len()), "the matrix dimensions do not match the buffer");
+        Self{
+            buf,
+            rows,
+            cols,
+            order,
+        }
+    }
+
+    /// Switches the matrix to the given order, moving the elements in place.
+    pub fn with_order(mut self, order: Order) -> Self{
+        self.set_order(order);
+        self
+    }
+
+    /// Switches the matrix to the given order, moving the elements in place.
+    pub fn set_order(&mut self, order: Order){
+        if order != self.order{
+            // the storage of a matrix in one order is its transpose in the other order
+            let (rows, cols) = match self.order{
+                Order::RowMajor => (self.rows, self.cols),
+                Order::ColumnMajor => (self.cols, self.rows),
+            };
+            self.transpose_storage(rows, cols);
+            self.order = order;
+        }
+    }
+
+    /// Returns the order the elements are stored in.
+    pub fn order(&self) -> Order{
+        self.order
+    }
+
+    /// Returns the number of rows.
+    pub fn rows(&self) -> usize{
+        self.rows
+    }
+
+    /// Returns the number of columns.
+    pub fn cols(&self) -> usize{
+        self.cols
+    }
+
+    /// Returns true if the matrix has as many rows as columns.
+    pub fn is_square(&self) -> bool{
+        self.rows == self.cols
+    }
+
+    /// Returns a reference to the element at `row`, `col`, or `None` if it is out of bounds.
+    pub fn get(&self, row:usize, col:usize) -> Option<&T>{
+        if row < self.rows && col < self.cols{
+            return Some(unsafe {self.buf.get_unchecked(self.offset(row, col))})
+        }
+        None
+    }
+
+    /// Returns a mutable reference to the element at `row`, `col`, or `None` if it is out of bounds.
+    pub fn get_mut(&mut self, row:usize, col:usize) -> Option<&mut T>{
+        if row < self.rows && col < self.cols{
+            let offset = self.offset(row, col);
+            return Some(unsafe {self.buf.get_mut_unchecked(offset)})
+        }
+        None
+    }
+
+    /// Returns an iterator over the elements of a row. Panics if `row` is out of bounds.
+    pub fn row(&self, row:usize) -> Lane<'_,T,N>{
+        assert!(row < self.rows, "row {} is out of bounds for a matrix with {} rows", row, self.rows);
+        let step = match self.order{
+            Order::RowMajor => 1,
+            Order::ColumnMajor => self.rows,
+        };
+        Lane::new(&self.buf, self.offset(row, 0), step, self.cols)
+    }
+
+    /// Returns an iterator over the elements of a column. Panics if `col` is out of bounds.
+    pub fn col(&self, col:usize) -> Lane<'_,T,N>{
+        assert!(col < self.cols, "column {} is out of bounds for a matrix with {} columns", col, self.cols);
+        let step = match self.order{
+            Order::RowMajor => self.cols,
+            Order::ColumnMajor => 1,
+        };
+        Lane::new(&self.buf, self.offset(0, col), step, self.rows)
+    }
+
+    /// Transposes the matrix in place, keeping its order.
+    pub fn transpose(&mut self){
+        // the storage read in the other order is already the transpose, it only has to be moved back
+        let order = self.order;
+        self.order = match order{
+            Order::RowMajor => Order::ColumnMajor,
+            Order::ColumnMajor => Order::RowMajor,
+        };
+        core::mem::swap(&mut self.rows, &mut self.cols);
+        self.set_order(order);
+    }
+
+    /// Returns the underlying SmartBuffer, with the elements in the order of the matrix.
+    pub fn as_buffer(&self) -> &SmartBuffer<T,N>{
+        &self.buf
+    }
+
+    /// Consumes the matrix and returns the underlying SmartBuffer.
+    pub fn into_buffer(self) -> SmartBuffer<T,N>{
+        self.buf
+    }
+
+    fn offset(&self, row:usize, col:usize) -> usize{
+        match self.order{
+            Order::RowMajor => row * self.cols + col,
+            Order::ColumnMajor => col * self.rows + row,
+        }
+    }
+
+    /// Transposes the storage of a row-major `rows` x `cols` matrix in place by following the
+    /// cycles of the permutation, so no scratch memory is needed.
+    fn transpose_storage(&mut self, rows:usize, cols:usize){
+        let len = rows * cols;
+        let dest = |p: usize| (p % cols) * rows + p / cols;
+        for start in 0..len{
+            // only move each cycle once, from its smallest index
+            let mut p = dest(start);
+            while p > start{
+                p = dest(p);
+            }
+            if p < start{
+                continue
+            }
+            let mut p = dest(start);
+            while p != start{
+                self.buf.swap_slots(start, p);
+                p = dest(p);
+            }
+        }
+    }
+}
+
+impl<T, const N:usize> Index<(usize, usize)> for SmartMatrix<T,N>
+    where T: Clone
+{
+    type Output = T;
+
+    fn index(&self, (row, col): (usize, usize)) -> &Self::Output {
+        match self.get(row, col){
+            Some(elem) => elem,
+            None => panic!("index ({}, {}) is out of bounds for a {}x{} matrix", row, col, self.rows, self.cols),
+        }
+    }
+}
+
+impl<T, const N:usize> IndexMut<(usize, usize)> for SmartMatrix<T,N>
+    where T: Clone
+{
+    fn index_mut(&mut self, (row, col): (usize, usize)) -> &mut Self::Output {
+        let (rows, cols) = (self.rows, self.cols);
+        match self.get_mut(row, col){
+            Some(elem) => elem,
+            None => panic!("index ({}, {}) is out of bounds for a {}x{} matrix", row, col, rows, cols),
+        }
+    }
+}
+
+impl<T, const N:usize> Debug for SmartMatrix<T,N>
+    where T: Clone + Debug
+{
+    /// Prints the matrix as a list of rows, whatever its order.
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_list()
+            .entries((0..self.rows).map(|row| RowDebug(self.row(row))))
+            .finish()
+    }
+}
+
+struct RowDebug<'a, T, const N:usize>(Lane<'a,T,N>)
+    where T: Clone;
+
+impl<T, const N:usize> Debug for RowDebug<'_,T,N>
+    where T: Clone + Debug
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_list().entries(self.0.clone()).finish()
+    }
+}
+
+/// Iterator over a row or a column of a SmartMatrix
+#[derive(Clone)]
+pub struct Lane<'a, T, const N:usize>
+    where T: Clone
+{
+    buf: &'a SmartBuffer<T,N>,
+    next: usize,
+    step: usize,
+    remaining: usize,
+}
+
+impl<'a, T, const N:usize> Lane<'a,T,N>
+    where T: Clone
+{
+    fn new(buf: &'a SmartBuffer<T,N>, next:usize, step:usize, remaining:usize) -> Self{
+        Self{
+            buf,
+            next,
+            step,
+            remaining,
+        }
+    }
+}
+
+impl<'a, T, const N:usize> Iterator for Lane<'a,T,N>
+    where T: Clone
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0{
+            return None
+        }
+        let elem = unsafe {self.buf.get_unchecked(self.next)};
+        self.remaining -= 1;
+        if self.remaining > 0{
+            self.next += self.step;
+        }
+        Some(elem)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T, const N:usize> DoubleEndedIterator for Lane<'_,T,N>
+    where T: Clone
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0{
+            return None
+        }
+        self.remaining -= 1;
+        Some(unsafe {self.buf.get_unchecked(self.next + self.remaining * self.step)})
+    }
+}
+
+impl<T, const N:usize> ExactSizeIterator for Lane<'_,T,N>
+    where T: Clone {}
+
+impl<T, const N:usize> FusedIterator for Lane<'_,T,N>
+    where T: Clone {}
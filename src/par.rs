@@ -0,0 +1,85 @@
+//! Parallel iteration with rayon.
+//!
+//! Work is split at the boundary between the stack and the heap first, then within each part.
+
+use crate::SmartBuffer;
+
+use alloc::vec::Vec;
+use core::ptr;
+use rayon::iter::{Chain, FromParallelIterator, IndexedParallelIterator, IntoParallelIterator, ParallelExtend, ParallelIterator, Take};
+
+impl<'a, T, const N: usize> IntoParallelIterator for &'a SmartBuffer<T,N>
+    where T: Clone + Sync
+{
+    type Iter = Chain<rayon::slice::Iter<'a, T>, rayon::slice::Iter<'a, T>>;
+    type Item = &'a T;
+
+    fn into_par_iter(self) -> Self::Iter {
+        let (stack, heap) = self.as_slices();
+        stack.into_par_iter().chain(heap)
+    }
+}
+
+impl<'a, T, const N: usize> IntoParallelIterator for &'a mut SmartBuffer<T,N>
+    where T: Clone + Send
+{
+    type Iter = Chain<rayon::slice::IterMut<'a, T>, rayon::slice::IterMut<'a, T>>;
+    type Item = &'a mut T;
+
+    fn into_par_iter(self) -> Self::Iter {
+        let (stack, heap) = self.as_mut_slices();
+        stack.into_par_iter().chain(heap)
+    }
+}
+
+impl<T, const N: usize> IntoParallelIterator for SmartBuffer<T,N>
+    where T: Clone + Send
+{
+    type Iter = Chain<Take<rayon::array::IntoIter<T, N>>, rayon::vec::IntoIter<T>>;
+    type Item = T;
+
+    /// Creates a consuming parallel iterator. The heap is handed over to a `Vec` without copying.
+    fn into_par_iter(self) -> Self::Iter {
+        let parts = self.into_raw_parts();
+        let stack_len = parts.size.min(parts.inline);
+        let heap_len = parts.capacity - parts.inline;
+        let live = parts.size - stack_len;
+
+        let heap = match parts.d_buf{
+            Some(ptr) => unsafe {
+                ptr::drop_in_place(ptr::slice_from_raw_parts_mut(ptr.add(live), heap_len - live));
+                // allocate uses Layout::array::<T>(heap_len) from the global allocator, just like Vec
+                Vec::from_raw_parts(ptr, live, heap_len)
+            },
+            None => Vec::new(),
+        };
+        parts.s_buf.into_par_iter().take(stack_len).chain(heap)
+    }
+}
+
+impl<T, const N: usize> FromParallelIterator<T> for SmartBuffer<T,N>
+    where T: Clone + Default + Send
+{
+    fn from_par_iter<I>(par_iter: I) -> Self
+    where I: IntoParallelIterator<Item = T>
+    {
+        let mut buf = Self::default();
+        buf.par_extend(par_iter);
+        buf
+    }
+}
+
+impl<T, const N: usize> ParallelExtend<T> for SmartBuffer<T,N>
+    where T: Clone + Send
+{
+    /// Collects the items in parallel, then moves them in with at most one allocation.
+    fn par_extend<I>(&mut self, par_iter: I)
+    where I: IntoParallelIterator<Item = T>
+    {
+        let items: Vec<T> = par_iter.into_par_iter().collect();
+        self.reserve_exact(items.len());
+        for item in items{
+            self.push(item);
+        }
+    }
+}
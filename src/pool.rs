@@ -0,0 +1,216 @@
+//! Recycling of heap segments between SmartBuffers.
+//!
+//! Buffers created with [`SmartBufferPool::get`] allocate their overflow region through the
+//! pool. When it is freed, on drop or when the buffer shrinks, the segment goes onto a free list
+//! instead of back to the allocator, and the next buffer that spills picks it up again.
+//!
+//! Segments are rounded up to a size class, the next power of two in elements, so a segment can
+//! serve any request of its class. A buffer also grows in place until it outgrows its class.
+//! The pool keeps at most `max_retained_bytes` on its free lists, anything past that is freed.
+//!
+//! Without the `std` feature the pool is for a single thread. With it the free lists sit behind
+//! a mutex, and the pool and its buffers can be sent to and shared between threads.
+
+use crate::SmartBuffer;
+use crate::error::TryReserveError;
+use crate::storage::{Global, Storage};
+
+use alloc::vec::Vec;
+use core::fmt::{self, Debug, Formatter};
+use core::mem;
+use core::ptr::NonNull;
+
+#[cfg(not(feature = "std"))]
+type Shared<T> = alloc::rc::Rc<core::cell::RefCell<FreeLists<T>>>;
+#[cfg(feature = "std")]
+type Shared<T> = alloc::sync::Arc<std::sync::Mutex<FreeLists<T>>>;
+
+/// The free segments of a pool, one list per size class.
+struct FreeLists<T>{
+    classes: Vec<Vec<NonNull<T>>>,
+    retained: usize,
+    max_retained: usize,
+}
+
+// the segments are owned by the free lists, and only handed out to one buffer at a time
+#[cfg(feature = "std")]
+unsafe impl<T: Send> Send for FreeLists<T> {}
+
+impl<T> FreeLists<T>{
+    /// Takes a segment of class `class` off its free list.
+    fn pop(&mut self, class:usize) -> Option<NonNull<T>>{
+        let ptr = self.classes.get_mut(class)?.pop()?;
+        self.retained -= class_bytes::<T>(class);
+        Some(ptr)
+    }
+
+    /// Puts a segment of class `class` on its free list, or hands it back if the cap is reached.
+    fn push(&mut self, ptr: NonNull<T>, class:usize) -> Option<NonNull<T>>{
+        let bytes = class_bytes::<T>(class);
+        if self.retained + bytes > self.max_retained{
+            return Some(ptr)
+        }
+        if self.classes.len() <= class{
+            self.classes.resize_with(class + 1, Vec::new);
+        }
+        self.classes[class].push(ptr);
+        self.retained += bytes;
+        None
+    }
+
+    /// Frees every retained segment.
+    fn release(&mut self){
+        for (class, list) in self.classes.iter_mut().enumerate(){
+            for ptr in list.drain(..){
+                unsafe {Global.deallocate(ptr, 1 << class)};
+            }
+        }
+        self.retained = 0;
+    }
+}
+
+impl<T> Drop for FreeLists<T>{
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+/// The size class of a segment of `slots` elements.
+fn class_of(slots:usize) -> Option<usize>{
+    slots.checked_next_power_of_two().map(usize::trailing_zeros).map(|class| class as usize)
+}
+
+fn class_bytes<T>(class:usize) -> usize{
+    mem::size_of::<T>() << class
+}
+
+/// A pool of heap segments shared by the SmartBuffers it hands out. Cloning the pool gives
+/// another handle to the same free lists.
+///
+/// ```
+/// use smart_buffer::pool::SmartBufferPool;
+///
+/// let pool = SmartBufferPool::<u8, 16>::new(64 * 1024);
+/// for _ in 0..3{
+///     let mut request = pool.get(0);
+///     request.resize(100, 1); // spills, into the same segment every time
+/// }
+/// assert_eq!(pool.retained_bytes(), 128);
+/// ```
+pub struct SmartBufferPool<T, const N:usize>{
+    storage: Pooled<T>,
+}
+
+/// A SmartBuffer whose heap segments come from a [`SmartBufferPool`].
+pub type PooledBuffer<T, const N:usize> = SmartBuffer<T, N, Pooled<T>>;
+
+impl<T, const N:usize> SmartBufferPool<T,N>
+    where T: Clone
+{
+    /// Creates an empty pool that keeps at most `max_retained_bytes` of freed segments.
+    pub fn new(max_retained_bytes:usize) -> Self{
+        let lists = FreeLists{
+            classes: Vec::new(),
+            retained: 0,
+            max_retained: max_retained_bytes,
+        };
+        Self{
+            storage: Pooled{
+                shared: Shared::new(lists.into()),
+            },
+        }
+    }
+
+    /// Creates an empty SmartBuffer with no heap whose segments come from this pool, where the
+    /// default value is set to the value entered.
+    pub fn get(&self, value: T) -> PooledBuffer<T,N>{
+        SmartBuffer::from_default_in(value, self.storage.clone())
+    }
+
+    /// Returns the number of bytes kept on the free lists.
+    pub fn retained_bytes(&self) -> usize{
+        self.storage.with(|lists| lists.retained)
+    }
+
+    /// Frees every segment on the free lists. Segments in use by buffers are not affected.
+    pub fn release(&self){
+        self.storage.with(FreeLists::release);
+    }
+}
+
+impl<T, const N:usize> Clone for SmartBufferPool<T,N>{
+    fn clone(&self) -> Self {
+        Self{
+            storage: self.storage.clone(),
+        }
+    }
+}
+
+impl<T, const N:usize> Debug for SmartBufferPool<T,N>{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.storage.with(|lists| f.debug_struct("SmartBufferPool")
+            .field("retained_bytes", &lists.retained)
+            .field("max_retained_bytes", &lists.max_retained)
+            .finish())
+    }
+}
+
+/// The storage of the buffers handed out by a [`SmartBufferPool`], a handle to its free lists.
+pub struct Pooled<T>{
+    shared: Shared<T>,
+}
+
+impl<T> Pooled<T>{
+    #[cfg(not(feature = "std"))]
+    fn with<R>(&self, f: impl FnOnce(&mut FreeLists<T>) -> R) -> R{
+        f(&mut self.shared.borrow_mut())
+    }
+
+    #[cfg(feature = "std")]
+    fn with<R>(&self, f: impl FnOnce(&mut FreeLists<T>) -> R) -> R{
+        // the free lists are consistent between calls, so a panic elsewhere does not matter
+        f(&mut self.shared.lock().unwrap_or_else(std::sync::PoisonError::into_inner))
+    }
+}
+
+impl<T> Clone for Pooled<T>{
+    fn clone(&self) -> Self {
+        Self{
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Debug for Pooled<T>{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("Pooled")
+    }
+}
+
+unsafe impl<T> Storage<T> for Pooled<T> {
+    fn allocate(&mut self, slots: usize) -> Result<NonNull<T>, TryReserveError> {
+        if mem::size_of::<T>() == 0{
+            return Global.allocate(slots)
+        }
+        let class = class_of(slots).ok_or(TryReserveError::CapacityOverflow)?;
+        match self.with(|lists| lists.pop(class)){
+            Some(ptr) => Ok(ptr),
+            None => Global.allocate(1 << class),
+        }
+    }
+
+    unsafe fn deallocate(&mut self, ptr: NonNull<T>, slots: usize) {
+        if mem::size_of::<T>() == 0{
+            return Global.deallocate(ptr, slots)
+        }
+        // the class was valid when the segment was allocated
+        let class = class_of(slots).unwrap();
+        if let Some(ptr) = self.with(|lists| lists.push(ptr, class)){
+            Global.deallocate(ptr, 1 << class);
+        }
+    }
+
+    unsafe fn grow_in_place(&mut self, _ptr: NonNull<T>, old_slots: usize, new_slots: usize) -> bool {
+        mem::size_of::<T>() != 0 && class_of(old_slots) == class_of(new_slots)
+    }
+}
@@ -0,0 +1,70 @@
+use crate::SmartBuffer;
+use crate::spill::Spill;
+
+use core::fmt::Debug;
+use proptest::arbitrary::{any_with, Arbitrary};
+use proptest::collection::vec;
+use proptest::option;
+use proptest::prelude::{BoxedStrategy, Just, Strategy};
+use proptest::prop_oneof;
+
+/// Lengths around the stack capacity: empty, exactly `N`, `N + 1`, a large spill, or anything
+/// that fits on the stack. Each of the edge cases is as likely as the two ranges.
+pub fn len<const N:usize>() -> impl Strategy<Value = usize>{
+    prop_oneof![
+        Just(0),
+        Just(N),
+        Just(N + 1),
+        (N + 2)..=(4 * N + 64),
+        0..=N,
+    ]
+}
+
+/// Either spill strategy, with `unspill_below` thresholds up to `N + 1`.
+pub fn spill<const N:usize>() -> impl Strategy<Value = Spill>{
+    prop_oneof![
+        Just(Spill::Split),
+        option::of(0..=N + 1).prop_map(|unspill_below| Spill::All {unspill_below}),
+    ]
+}
+
+/// A SmartBuffer with a [`len`] number of elements, generated by `element` like its default value,
+/// and a random [`spill`] strategy.
+///
+/// ```
+/// use proptest::prelude::*;
+/// use smart_buffer::prop;
+///
+/// proptest!(|(buf in prop::buffer::<_, _, 8>(any::<u16>()))| {
+///     let total: u32 = buf.iter().map(|&x| u32::from(x)).sum();
+///     prop_assert!(total <= u32::from(u16::MAX) * buf.len() as u32);
+/// });
+/// ```
+pub fn buffer<T, S, const N:usize>(element: S) -> impl Strategy<Value = SmartBuffer<T,N>>
+    where T: Clone + Debug,
+        S: Strategy<Value = T> + Clone
+{
+    (element.clone(), spill::<N>(), len::<N>())
+        .prop_flat_map(move |(default, spill, len)| (Just(default), Just(spill), vec(element.clone(), len)))
+        .prop_map(|(default, spill, elems)| {
+            let mut buf = SmartBuffer::from_default(default).with_spill(spill);
+            buf.reserve_exact(elems.len());
+            for elem in elems{
+                buf.push(elem);
+            }
+            buf
+        })
+}
+
+/// Generates buffers with [`buffer`], so `any::<SmartBuffer<T, N>>()` hits the same edge cases.
+impl<T, const N:usize> Arbitrary for SmartBuffer<T,N>
+    where T: Arbitrary + Clone + 'static,
+        T::Strategy: 'static
+{
+    type Parameters = T::Parameters;
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(args: Self::Parameters) -> Self::Strategy{
+        buffer(any_with::<T>(args).boxed()).boxed()
+    }
+}
@@ -0,0 +1,199 @@
+use crate::SmartBuffer;
+use crate::error::TryReserveError;
+use crate::iter::SmartBufferIterRef;
+
+use core::fmt::{Debug, Formatter};
+use core::ops::{Index, IndexMut};
+use zeroize::Zeroize;
+
+/// A SmartBuffer for secrets such as keys and passwords.
+///
+/// Every slot, on the stack and on the heap, is zeroized when the buffer is dropped, cleared or
+/// truncated, and heap memory is wiped before it is freed when the buffer grows or shrinks.
+/// `Debug` only shows the size. The SmartBuffer inside is not exposed, so the elements can only
+/// be read through the accessors below.
+///
+/// ```
+/// use smart_buffer::secure::SecureBuffer;
+///
+/// let mut key = SecureBuffer::<u8, 32>::new(0, 32);
+/// key.insert_slice(b"correct horse battery staple");
+/// assert_eq!(format!("{:?}", key), "SecureBuffer { size: 28, .. }");
+/// ```
+///
+/// ```compile_fail
+/// # use smart_buffer::secure::SecureBuffer;
+/// let key = SecureBuffer::<u8, 32>::new(0, 32);
+/// println!("{:?}", *key); // no way around the redacted Debug
+/// ```
+pub struct SecureBuffer<T, const N:usize>
+    where T: Clone + Zeroize
+{
+    buf: SmartBuffer<T,N>,
+}
+
+impl<T, const N:usize> SecureBuffer<T,N>
+    where T: Clone + Zeroize
+{
+    /// Creates a SecureBuffer where the default value is set to the value entered, see [`SmartBuffer::new`].
+    pub fn new(value: T, len:usize) -> Self
+    where T: Copy
+    {
+        Self::from(SmartBuffer::new(value, len))
+    }
+
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize{
+        self.buf.len()
+    }
+
+    /// Returns true if the SecureBuffer holds no elements.
+    pub fn is_empty(&self) -> bool{
+        self.buf.is_empty()
+    }
+
+    /// Returns the number of elements the SecureBuffer can hold, stack and heap combined.
+    pub fn capacity(&self) -> usize{
+        self.buf.capacity()
+    }
+
+    /// Safely get a value at an index
+    pub fn get(&self, index:usize) -> Option<&T>{
+        self.buf.get(index)
+    }
+
+    /// See [`SmartBuffer::as_slices`].
+    pub fn as_slices(&self) -> (&[T], &[T]){
+        self.buf.as_slices()
+    }
+
+    /// Returns an iterator over the elements.
+    pub fn iter(&self) -> SmartBufferIterRef<'_,T,N>{
+        self.buf.iter()
+    }
+
+    /// Safely push a value into the SecureBuffer
+    pub fn push(&mut self, other: T){
+        self.buf.push(other);
+    }
+
+    /// Safely inserts a slice of data, starting at the size.
+    pub fn insert_slice(&mut self, slice: &[T]){
+        self.buf.insert_slice(slice);
+    }
+
+    /// Safely insert a value into the SecureBuffer, zeroizing the value it overwrites.
+    pub fn insert(&mut self, other: T, index: usize){
+        if index < self.buf.capacity{
+            unsafe {self.buf.get_mut_unchecked(index).zeroize()};
+        }
+        self.buf.insert(other, index);
+    }
+
+    /// Zeroizes every slot, then sets it back to the default value and the size to zero.
+    pub fn clear(&mut self){
+        self.zeroize_from(0);
+        self.buf.clear();
+    }
+
+    /// Shortens the buffer to `len` elements, zeroizing the removed ones.
+    pub fn truncate(&mut self, len:usize){
+        if len < self.buf.size{
+            for i in len..self.buf.size{
+                unsafe {self.buf.get_mut_unchecked(i).zeroize()};
+            }
+            self.buf.truncate(len);
+        }
+    }
+
+    /// See [`SmartBuffer::reserve`].
+    pub fn reserve(&mut self, additional:usize){
+        self.buf.reserve(additional);
+    }
+
+    /// See [`SmartBuffer::reserve_exact`].
+    pub fn reserve_exact(&mut self, additional:usize){
+        self.buf.reserve_exact(additional);
+    }
+
+    /// See [`SmartBuffer::try_reserve`].
+    pub fn try_reserve(&mut self, additional:usize) -> Result<(), TryReserveError>{
+        self.buf.try_reserve(additional)
+    }
+
+    /// See [`SmartBuffer::shrink_to`].
+    pub fn shrink_to(&mut self, min_capacity:usize){
+        self.buf.shrink_to(min_capacity);
+    }
+
+    /// See [`SmartBuffer::shrink_to_fit`].
+    pub fn shrink_to_fit(&mut self){
+        self.buf.shrink_to_fit();
+    }
+
+    /// Zeroizes every slot from `start` up to the capacity.
+    fn zeroize_from(&mut self, start:usize){
+        for i in start..self.buf.capacity{
+            unsafe {self.buf.get_mut_unchecked(i).zeroize()};
+        }
+    }
+}
+
+impl<T, const N:usize> From<SmartBuffer<T,N>> for SecureBuffer<T,N>
+    where T: Clone + Zeroize
+{
+    /// Takes over a SmartBuffer. Copies it left behind in earlier reallocations are not wiped.
+    fn from(mut buf: SmartBuffer<T,N>) -> Self {
+        buf.wipe = true;
+        Self{
+            buf
+        }
+    }
+}
+
+impl<T, const N:usize> Index<usize> for SecureBuffer<T,N>
+    where T: Clone + Zeroize
+{
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.buf[index]
+    }
+}
+
+impl<T, const N:usize> IndexMut<usize> for SecureBuffer<T,N>
+    where T: Clone + Zeroize
+{
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.buf[index]
+    }
+}
+
+impl<T, const N:usize> Drop for SecureBuffer<T,N>
+    where T: Clone + Zeroize
+{
+    fn drop(&mut self) {
+        self.zeroize_from(0);
+        self.buf.default.zeroize();
+    }
+}
+
+impl<T, const N:usize> Debug for SecureBuffer<T,N>
+    where T: Clone + Zeroize
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SecureBuffer")
+            .field("size", &self.buf.size)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T, const N:usize> Clone for SecureBuffer<T,N>
+    where T: Clone + Zeroize
+{
+    fn clone(&self) -> Self {
+        Self{
+            buf: self.buf.clone(),
+        }
+    }
+}
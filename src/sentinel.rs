@@ -0,0 +1,214 @@
+use crate::SmartBuffer;
+use crate::error::TryReserveError;
+use crate::storage::{Global, Storage};
+
+use core::ffi::CStr;
+use core::fmt;
+use core::ops::Deref;
+use core::slice;
+#[cfg(feature = "alloc")]
+use alloc::ffi::CString;
+
+impl<T, const N:usize, S> SmartBuffer<T,N,S>
+    where T: Clone,
+        S: Storage<T>
+{
+    /// Every slot up to the capacity, not just the elements, as the stack part and the heap part.
+    fn slots(&self) -> (&[T], &[T]){
+        let heap = match self.d_buf{
+            Some(ptr) => unsafe {slice::from_raw_parts(ptr, self.capacity - self.inline)},
+            None => &[],
+        };
+        (&self.s_buf[..self.inline], heap)
+    }
+
+    /// Returns the index of the first slot equal to `sentinel`, or `None` if there is none.
+    ///
+    /// Every slot up to the capacity is searched, not only the first `len` elements, so this also
+    /// finds the end of data written past the size, for example by foreign code.
+    pub fn len_until(&self, sentinel: &T) -> Option<usize>
+    where T: PartialEq
+    {
+        let (stack, heap) = self.slots();
+        stack.iter().chain(heap).position(|elem| elem == sentinel)
+    }
+
+    /// Sets the size to [`len_until`](Self::len_until) and returns it. Without a sentinel the size
+    /// is left untouched.
+    ///
+    /// ```
+    /// use smart_buffer::SmartBuffer;
+    ///
+    /// let mut name = SmartBuffer::<u8, 16>::new(0, 16);
+    /// name[0] = b'o';
+    /// name[1] = b'k';
+    /// assert_eq!(name.set_len_from_sentinel(&0), Some(2));
+    /// assert_eq!(name.len(), 2);
+    /// ```
+    pub fn set_len_from_sentinel(&mut self, sentinel: &T) -> Option<usize>
+    where T: PartialEq
+    {
+        let len = self.len_until(sentinel)?;
+        self.set_size(len);
+        Some(len)
+    }
+}
+
+impl<const N:usize, S> SmartBuffer<u8,N,S>
+    where S: Storage<u8>
+{
+    /// Returns the NUL-terminated string at the start of the SmartBuffer, searching every slot up
+    /// to the capacity for the terminator.
+    ///
+    /// Returns `None` if there is no terminator, or if it lies on the heap while the start of the
+    /// string is on the stack, so the string is not contiguous. [`Spill::All`](crate::spill::Spill::All)
+    /// avoids the latter.
+    pub fn as_c_str(&self) -> Option<&CStr>{
+        let first = match self.slots(){
+            ([], heap) => heap,
+            (stack, _) => stack,
+        };
+        CStr::from_bytes_until_nul(first).ok()
+    }
+
+    /// Copies the elements up to the size, or up to the first NUL before that, into a CString.
+    #[cfg(feature = "alloc")]
+    pub fn to_c_string(&self) -> CString{
+        let (stack, heap) = self.as_slices();
+        let bytes = stack.iter().chain(heap).copied().take_while(|&byte| byte != 0).collect::<alloc::vec::Vec<u8>>();
+        CString::new(bytes).expect("the bytes stop at the first NUL")
+    }
+
+    /// Returns a writer that keeps a NUL byte in the slot right after the last element, so the
+    /// contents can be handed to C at any point with [`as_c_str`](Self::as_c_str).
+    ///
+    /// ```
+    /// use smart_buffer::SmartBuffer;
+    /// use smart_buffer::spill::Spill;
+    /// use core::fmt::Write;
+    ///
+    /// # #[cfg(feature = "alloc")] {
+    /// let mut path = SmartBuffer::<u8, 8>::new(0xff, 8).with_spill(Spill::All {unspill_below: None});
+    /// let mut w = path.with_nul_terminator();
+    /// write!(w, "/dev/ttyUSB{}", 0).unwrap();
+    /// assert_eq!(w.as_c_str().unwrap().to_bytes(), b"/dev/ttyUSB0");
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    /// Panics like [`reserve`](Self::reserve) if there is no room for the terminator, see
+    /// [`try_with_nul_terminator`](Self::try_with_nul_terminator).
+    pub fn with_nul_terminator(&mut self) -> NulTerminated<'_,N,S>{
+        let mut writer = NulTerminated{
+            buf: self,
+        };
+        writer.put_slice(&[]);
+        writer
+    }
+
+    /// Fallible version of [`with_nul_terminator`](Self::with_nul_terminator), which returns the
+    /// error if there is no room for the terminator.
+    pub fn try_with_nul_terminator(&mut self) -> Result<NulTerminated<'_,N,S>, TryReserveError>{
+        let mut writer = NulTerminated{
+            buf: self,
+        };
+        writer.try_put_slice(&[])?;
+        Ok(writer)
+    }
+}
+
+impl<const N:usize> SmartBuffer<u8,N>{
+    /// Copies the bytes of a C string like the `From<&CStr>` impl, or returns the error if they
+    /// and the terminator do not fit, which without the `alloc` feature means more than `N - 1`
+    /// bytes.
+    pub fn try_from_c_str(string: &CStr) -> Result<Self, TryReserveError>{
+        let mut buf = Self::from_default(0);
+        buf.try_with_nul_terminator()?.try_put_slice(string.to_bytes())?;
+        Ok(buf)
+    }
+}
+
+impl<const N:usize> From<&CStr> for SmartBuffer<u8,N>{
+    /// Copies the bytes of a C string, keeping its terminator in the slot after the last element.
+    ///
+    /// Panics if they do not fit, see [`SmartBuffer::try_from_c_str`].
+    fn from(string: &CStr) -> Self {
+        let mut buf = Self::from_default(0);
+        buf.with_nul_terminator().put_slice(string.to_bytes());
+        buf
+    }
+}
+
+/// Writes bytes into a SmartBuffer while keeping it NUL-terminated, see [`SmartBuffer::with_nul_terminator`].
+///
+/// The writing methods panic like [`SmartBuffer::reserve`] when the storage has no room for the
+/// bytes and the terminator. `try_push` and `try_put_slice` return the error instead, and so
+/// does writing through [`fmt::Write`].
+pub struct NulTerminated<'a, const N:usize, S = Global>
+    where S: Storage<u8>
+{
+    buf: &'a mut SmartBuffer<u8,N,S>,
+}
+
+impl<const N:usize, S> NulTerminated<'_,N,S>
+    where S: Storage<u8>
+{
+    /// Appends a byte.
+    pub fn push(&mut self, byte: u8){
+        self.put_slice(&[byte]);
+    }
+
+    /// Appends a slice of bytes, followed by the terminator.
+    pub fn put_slice(&mut self, bytes: &[u8]){
+        self.buf.reserve(bytes.len() + 1);
+        self.buf.insert_slice(bytes);
+        self.terminate();
+    }
+
+    /// Appends a byte, or returns the error and leaves the contents unchanged if there is no room.
+    pub fn try_push(&mut self, byte: u8) -> Result<(), TryReserveError>{
+        self.try_put_slice(&[byte])
+    }
+
+    /// Appends a slice of bytes followed by the terminator, or returns the error and leaves the
+    /// contents unchanged if there is no room for all of them.
+    pub fn try_put_slice(&mut self, bytes: &[u8]) -> Result<(), TryReserveError>{
+        self.buf.try_reserve(bytes.len() + 1)?;
+        self.buf.insert_slice(bytes);
+        self.terminate();
+        Ok(())
+    }
+
+    /// Shortens the contents to `len` bytes, moving the terminator with them.
+    pub fn truncate(&mut self, len:usize){
+        self.buf.truncate(len);
+        // truncating may have given the heap back, so make sure the slot still exists
+        self.buf.reserve(1);
+        self.terminate();
+    }
+
+    /// Writes the terminator into the slot after the last element, which must exist.
+    fn terminate(&mut self){
+        let end = self.buf.size;
+        self.buf.record_slots(end..end + 1);
+        unsafe {*self.buf.get_mut_unchecked(end) = 0};
+    }
+}
+
+impl<const N:usize, S> Deref for NulTerminated<'_,N,S>
+    where S: Storage<u8>
+{
+    type Target = SmartBuffer<u8,N,S>;
+
+    fn deref(&self) -> &Self::Target {
+        self.buf
+    }
+}
+
+impl<const N:usize, S> fmt::Write for NulTerminated<'_,N,S>
+    where S: Storage<u8>
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.try_put_slice(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
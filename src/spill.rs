@@ -0,0 +1,101 @@
+use crate::SmartBuffer;
+use crate::storage::Storage;
+use core::mem::swap;
+use core::slice;
+
+/// Decides where the elements of a SmartBuffer live once it outgrows its stack.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Spill {
+    /// The first `N` elements stay on the stack and only the overflow goes to the heap. This is the default.
+    #[default]
+    Split,
+    /// As soon as a heap is allocated every element moves onto it, so the data is always contiguous.
+    ///
+    /// - `unspill_below` moves the data back onto the stack and frees the heap once the size drops
+    ///   below it (and fits in `N`). `None` keeps the heap until it is shrunk explicitly.
+    All { unspill_below: Option<usize> },
+}
+
+impl<T, const N:usize, S> SmartBuffer<T,N,S>
+    where T: Clone,
+        S: Storage<T>
+{
+    /// Switches the SmartBuffer to the given spill strategy, moving the elements if needed.
+    ///
+    /// Switching a spilled SmartBuffer to [`Spill::All`] needs a heap with room for the stack
+    /// elements as well. If the storage cannot provide one, the SmartBuffer is returned unchanged
+    /// and keeps [`Spill::Split`], which [`spill`](Self::spill) reports.
+    ///
+    /// ```
+    /// use smart_buffer::SmartBuffer;
+    /// use smart_buffer::spill::Spill;
+    ///
+    /// # #[cfg(feature = "alloc")] {
+    /// let mut buf = SmartBuffer::<u8, 4>::new(0, 16).with_spill(Spill::All { unspill_below: Some(2) });
+    /// buf.insert_arr(&[1, 2, 3, 4, 5]);
+    /// assert_eq!(buf.as_slice(), Some(&[1, 2, 3, 4, 5][..]));
+    /// # }
+    /// ```
+    pub fn with_spill(mut self, spill: Spill) -> Self{
+        self.spill = spill;
+        match spill{
+            Spill::Split => {
+                if self.inline != N{
+                    self.unspill();
+                }
+            }
+            Spill::All {..} => {
+                if self.d_buf.is_some() && self.inline == N{
+                    // make room for the stack elements first, allocate then moves everything over
+                    let capacity = self.capacity;
+                    if self.allocate(capacity).is_err(){
+                        // allocate fails before touching anything
+                        self.spill = Spill::Split;
+                    }
+                }
+            }
+        }
+        self
+    }
+
+    /// Returns the spill strategy of the SmartBuffer.
+    pub fn spill(&self) -> Spill{
+        self.spill
+    }
+
+    /// Moves the stack elements to the front of the heap. The heap must have at least `size` and
+    /// at least `N` slots, as the last `N` heap slots end up on the stack.
+    pub(crate) fn spill_to_heap(&mut self){
+        let heap = self.capacity - N;
+        let slots = unsafe {slice::from_raw_parts_mut(self.d_buf.unwrap(), heap)};
+        slots.rotate_right(N.min(heap));
+        for (stack, heap) in self.s_buf.iter_mut().zip(slots.iter_mut()){
+            swap(stack, heap);
+        }
+        self.inline = 0;
+        self.capacity = heap;
+        self.size = self.size.min(self.capacity);
+        self.cursor = self.cursor.min(self.size);
+    }
+
+    /// Moves the first `N` heap elements back onto the stack, undoing `spill_to_heap`.
+    pub(crate) fn unspill(&mut self){
+        let heap = self.capacity;
+        let slots = unsafe {slice::from_raw_parts_mut(self.d_buf.unwrap(), heap)};
+        for (stack, heap) in self.s_buf.iter_mut().zip(slots.iter_mut()){
+            swap(stack, heap);
+        }
+        slots.rotate_left(N.min(heap));
+        self.inline = N;
+        self.capacity = N + heap;
+    }
+
+    /// Frees the heap if the size dropped below the `unspill_below` threshold.
+    pub(crate) fn maybe_unspill(&mut self){
+        if let Spill::All {unspill_below: Some(threshold)} = self.spill{
+            if self.inline != N && self.size < threshold && self.size <= N{
+                self.release();
+            }
+        }
+    }
+}
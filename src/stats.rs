@@ -0,0 +1,281 @@
+//! Spill telemetry, for choosing `N` from real traffic.
+//!
+//! Every heap allocation made by a SmartBuffer, and the peak length of every SmartBuffer when it
+//! is dropped, is recorded per element type and `N` in a global registry, and handed to the
+//! [`SpillObserver`] installed with [`set_observer`]. A SmartBuffer counts as a spill when it is
+//! dropped if it ever held more than `N` elements, however early its heap was allocated.
+//!
+//! ```
+//! use smart_buffer::SmartBuffer;
+//! use smart_buffer::stats;
+//!
+//! # #[cfg(feature = "alloc")] {
+//! let mut buf = SmartBuffer::<u16, 4>::new(0, 4);
+//! for i in 0..6{
+//!     buf.reserve(1);
+//!     buf.push(i);
+//! }
+//! drop(buf);
+//!
+//! let stats = stats::stats_for::<u16, 4>().unwrap();
+//! assert_eq!(stats.spills, 1);
+//! assert_eq!(stats.buffers(), 1);
+//! assert_eq!(stats.peak_len[3], 1); // a peak length in 4..8
+//! # }
+//! ```
+
+use core::cell::UnsafeCell;
+use core::fmt::{Display, Formatter};
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+/// The number of buckets in [`TypeStats::peak_len`].
+pub const BUCKETS: usize = usize::BITS as usize + 1;
+
+/// How many distinct SmartBuffer types the registry can hold. Types beyond that are only seen
+/// by the observer.
+pub const REGISTRY_SLOTS: usize = 128;
+
+/// A heap allocation made by a SmartBuffer, its first one or a resize of its heap.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub struct AllocEvent {
+    /// The element type, from [`core::any::type_name`].
+    pub type_name: &'static str,
+    /// `N`
+    pub inline_capacity: usize,
+    /// The number of elements at the time of the allocation.
+    pub len: usize,
+    /// Heap slots before the allocation, 0 if this is the first one.
+    pub old_heap_capacity: usize,
+    /// Heap slots after the allocation.
+    pub new_heap_capacity: usize,
+    /// The size of the new allocation in bytes.
+    pub heap_bytes: usize,
+}
+
+/// A SmartBuffer being dropped.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub struct DropEvent {
+    /// The element type, from [`core::any::type_name`].
+    pub type_name: &'static str,
+    /// `N`
+    pub inline_capacity: usize,
+    /// The largest number of elements the SmartBuffer held.
+    pub peak_len: usize,
+}
+
+impl DropEvent {
+    /// Returns true if the SmartBuffer ever held more than `N` elements.
+    pub fn spilled(&self) -> bool {
+        self.peak_len > self.inline_capacity
+    }
+}
+
+/// Receives the events of every SmartBuffer in the program, see [`set_observer`].
+///
+/// The callbacks run on the allocation and drop paths, so they should be cheap and must not
+/// panic.
+pub trait SpillObserver: Sync {
+    /// Called after a SmartBuffer allocated or resized its heap.
+    fn on_allocate(&self, event: &AllocEvent);
+
+    /// Called when a SmartBuffer is dropped.
+    fn on_drop(&self, event: &DropEvent) {
+        let _ = event;
+    }
+}
+
+/// The error returned when an observer is already installed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SetObserverError(());
+
+impl Display for SetObserverError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str("a spill observer is already installed")
+    }
+}
+
+struct NopObserver;
+
+impl SpillObserver for NopObserver {
+    fn on_allocate(&self, _: &AllocEvent) {}
+}
+
+const UNSET: u8 = 0;
+const SETTING: u8 = 1;
+const SET: u8 = 2;
+
+static OBSERVER_STATE: AtomicU8 = AtomicU8::new(UNSET);
+static mut OBSERVER: &dyn SpillObserver = &NopObserver;
+
+/// Installs the observer for the rest of the program. Only the first call succeeds.
+pub fn set_observer(observer: &'static dyn SpillObserver) -> Result<(), SetObserverError> {
+    match OBSERVER_STATE.compare_exchange(UNSET, SETTING, Ordering::Acquire, Ordering::Relaxed) {
+        Ok(_) => {
+            // nobody reads OBSERVER before the state is SET
+            unsafe { OBSERVER = observer };
+            OBSERVER_STATE.store(SET, Ordering::Release);
+            Ok(())
+        }
+        Err(_) => Err(SetObserverError(())),
+    }
+}
+
+fn observer() -> &'static dyn SpillObserver {
+    if OBSERVER_STATE.load(Ordering::Acquire) == SET {
+        unsafe { OBSERVER }
+    } else {
+        &NopObserver
+    }
+}
+
+/// What the registry knows about one SmartBuffer type.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct TypeStats {
+    /// The element type, from [`core::any::type_name`].
+    pub type_name: &'static str,
+    /// `N`
+    pub inline_capacity: usize,
+    /// How many of the dropped SmartBuffers held more than `N` elements.
+    pub spills: usize,
+    /// How many heap allocations were made, including ones that no element went past `N` for.
+    pub allocations: usize,
+    /// The sum of the sizes of all heap allocations in bytes.
+    pub heap_bytes_allocated: usize,
+    /// Histogram of the peak lengths of the dropped SmartBuffers. Bucket 0 counts the ones that
+    /// stayed empty, bucket `b` the ones that peaked in `2^(b-1)..2^b`.
+    pub peak_len: [usize; BUCKETS],
+}
+
+impl TypeStats {
+    /// Returns the number of SmartBuffers of this type that were dropped.
+    pub fn buffers(&self) -> usize {
+        self.peak_len.iter().sum()
+    }
+}
+
+const EMPTY: u8 = 0;
+const CLAIMED: u8 = 1;
+const READY: u8 = 2;
+
+struct Entry {
+    state: AtomicU8,
+    // written once while CLAIMED, read only once READY
+    key: UnsafeCell<(&'static str, usize)>,
+    spills: AtomicUsize,
+    allocations: AtomicUsize,
+    heap_bytes: AtomicUsize,
+    peak_len: [AtomicUsize; BUCKETS],
+}
+
+unsafe impl Sync for Entry {}
+
+impl Entry {
+    const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(EMPTY),
+            key: UnsafeCell::new(("", 0)),
+            spills: AtomicUsize::new(0),
+            allocations: AtomicUsize::new(0),
+            heap_bytes: AtomicUsize::new(0),
+            peak_len: [const { AtomicUsize::new(0) }; BUCKETS],
+        }
+    }
+
+    /// Returns the key once the entry is in use, waiting for a concurrent claim to finish.
+    fn key(&self) -> Option<(&'static str, usize)> {
+        loop {
+            match self.state.load(Ordering::Acquire) {
+                EMPTY => return None,
+                READY => return Some(unsafe { *self.key.get() }),
+                _ => core::hint::spin_loop(),
+            }
+        }
+    }
+
+    fn snapshot(&self) -> Option<TypeStats> {
+        let (type_name, inline_capacity) = self.key()?;
+        Some(TypeStats {
+            type_name,
+            inline_capacity,
+            spills: self.spills.load(Ordering::Relaxed),
+            allocations: self.allocations.load(Ordering::Relaxed),
+            heap_bytes_allocated: self.heap_bytes.load(Ordering::Relaxed),
+            peak_len: core::array::from_fn(|i| self.peak_len[i].load(Ordering::Relaxed)),
+        })
+    }
+}
+
+static REGISTRY: [Entry; REGISTRY_SLOTS] = [const { Entry::new() }; REGISTRY_SLOTS];
+
+/// Finds the entry for a key, or claims an empty one. Open addressing, entries are never removed.
+fn entry(type_name: &'static str, inline_capacity: usize, insert: bool) -> Option<&'static Entry> {
+    // FNV-1a
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for &byte in type_name.as_bytes().iter().chain(&inline_capacity.to_le_bytes()) {
+        hash = (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+    }
+    for probe in 0..REGISTRY_SLOTS {
+        let entry = &REGISTRY[(hash as usize).wrapping_add(probe) % REGISTRY_SLOTS];
+        if entry.key().is_none() {
+            if !insert {
+                return None
+            }
+            if entry.state.compare_exchange(EMPTY, CLAIMED, Ordering::Acquire, Ordering::Acquire).is_ok() {
+                unsafe { *entry.key.get() = (type_name, inline_capacity) };
+                entry.state.store(READY, Ordering::Release);
+                return Some(entry)
+            }
+        }
+        // somebody may have claimed it in the meantime, so look at the key again
+        if entry.key() == Some((type_name, inline_capacity)) {
+            return Some(entry)
+        }
+    }
+    None
+}
+
+/// Returns what the registry recorded for `SmartBuffer<T, N>`, or `None` if it has not seen one
+/// allocate or drop yet.
+pub fn stats_for<T, const N: usize>() -> Option<TypeStats> {
+    entry(core::any::type_name::<T>(), N, false)?.snapshot()
+}
+
+/// Returns what the registry recorded for every SmartBuffer type it has seen.
+pub fn all_stats() -> impl Iterator<Item = TypeStats> {
+    REGISTRY.iter().filter_map(Entry::snapshot)
+}
+
+pub(crate) fn record_allocate<T, const N: usize>(len: usize, old_heap_capacity: usize, new_heap_capacity: usize, heap_bytes: usize) {
+    let event = AllocEvent {
+        type_name: core::any::type_name::<T>(),
+        inline_capacity: N,
+        len,
+        old_heap_capacity,
+        new_heap_capacity,
+        heap_bytes,
+    };
+    if let Some(entry) = entry(event.type_name, N, true) {
+        entry.allocations.fetch_add(1, Ordering::Relaxed);
+        entry.heap_bytes.fetch_add(heap_bytes, Ordering::Relaxed);
+    }
+    observer().on_allocate(&event);
+}
+
+pub(crate) fn record_drop<T, const N: usize>(peak_len: usize) {
+    let event = DropEvent {
+        type_name: core::any::type_name::<T>(),
+        inline_capacity: N,
+        peak_len,
+    };
+    if let Some(entry) = entry(event.type_name, N, true) {
+        let bucket = (usize::BITS - peak_len.leading_zeros()) as usize;
+        entry.peak_len[bucket].fetch_add(1, Ordering::Relaxed);
+        if event.spilled() {
+            entry.spills.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    observer().on_drop(&event);
+}
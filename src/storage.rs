@@ -0,0 +1,174 @@
+//! Where the overflow region of a SmartBuffer lives.
+//!
+//! The elements past the stack go to a [`Storage`], chosen with the third type parameter of
+//! [`SmartBuffer`]. The default is [`Global`], the global allocator. Buffers
+//! on another storage are created with [`SmartBuffer::new_in`](crate::SmartBuffer::new_in) and
+//! have the same API otherwise, or with [`SmartBuffer::with_overflow`](crate::SmartBuffer::with_overflow)
+//! for memory the caller owns.
+
+use crate::SmartBuffer;
+use crate::error::TryReserveError;
+
+#[cfg(feature = "alloc")]
+use alloc::alloc::{alloc, dealloc};
+use core::alloc::Layout;
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+
+/// A source of memory for the overflow region of a SmartBuffer.
+///
+/// A SmartBuffer asks for one region at a time, always for at least one slot, and only writes
+/// initialised values into it. Every value is dropped before the region is given back.
+///
+/// # Safety
+/// A region returned by [`allocate`](Self::allocate) must be valid for reads and writes of
+/// `slots` elements of `T`, aligned for `T`, and not handed out again until it is passed to
+/// [`deallocate`](Self::deallocate). The same holds for the larger region after
+/// [`grow_in_place`](Self::grow_in_place) returns true.
+pub unsafe trait Storage<T> {
+    /// Returns uninitialised memory for `slots` elements.
+    fn allocate(&mut self, slots: usize) -> Result<NonNull<T>, TryReserveError>;
+
+    /// Gives back a region returned by [`allocate`](Self::allocate).
+    ///
+    /// # Safety
+    /// `ptr` must be a region of this storage that is `slots` long and still in use.
+    unsafe fn deallocate(&mut self, ptr: NonNull<T>, slots: usize);
+
+    /// Tries to extend a region from `old_slots` to `new_slots` without moving it. The elements in
+    /// it stay where they are. The default never can, so growing allocates a new region and moves
+    /// the elements over before the old one is given back.
+    ///
+    /// # Safety
+    /// `ptr` must be a region of this storage that is `old_slots` long and still in use, and
+    /// `new_slots` must be larger than `old_slots`.
+    unsafe fn grow_in_place(&mut self, ptr: NonNull<T>, old_slots: usize, new_slots: usize) -> bool {
+        let _ = (ptr, old_slots, new_slots);
+        false
+    }
+
+    /// The most slots a single region can hold. Growth never asks for more than this unless the
+    /// elements need it.
+    fn max_slots(&self) -> usize {
+        usize::MAX
+    }
+}
+
+/// The global allocator, the default storage. Without the `alloc` feature it has no memory to
+/// hand out, so a SmartBuffer on it never grows past its stack.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Global;
+
+unsafe impl<T> Storage<T> for Global {
+    fn allocate(&mut self, slots: usize) -> Result<NonNull<T>, TryReserveError> {
+        let layout = Layout::array::<T>(slots).map_err(|_| TryReserveError::CapacityOverflow)?;
+        if layout.size() == 0 {
+            return Ok(NonNull::dangling())
+        }
+        alloc_block(layout).map(NonNull::cast)
+    }
+
+    unsafe fn deallocate(&mut self, ptr: NonNull<T>, slots: usize) {
+        // the layout was valid when the region was allocated
+        let layout = Layout::array::<T>(slots).unwrap();
+        // zero-sized layouts were never really allocated
+        if layout.size() != 0 {
+            #[cfg(feature = "alloc")]
+            dealloc(ptr.as_ptr() as *mut u8, layout);
+            #[cfg(not(feature = "alloc"))]
+            let _ = ptr;
+        }
+    }
+}
+
+/// Allocates a block for `layout`, which must not be zero-sized.
+#[cfg(feature = "alloc")]
+fn alloc_block(layout: Layout) -> Result<NonNull<u8>, TryReserveError> {
+    NonNull::new(unsafe { alloc(layout) }).ok_or(TryReserveError::AllocError { layout })
+}
+
+/// Without `alloc` there is no heap, so the SmartBuffer can never grow past its stack.
+#[cfg(not(feature = "alloc"))]
+fn alloc_block(_layout: Layout) -> Result<NonNull<u8>, TryReserveError> {
+    Err(TryReserveError::CapacityOverflow)
+}
+
+/// Memory owned by the caller, such as a statically reserved region, used as the overflow region
+/// of one SmartBuffer. See [`SmartBuffer::with_overflow`].
+///
+/// It hands out its slots as one region that grows in place up to the length of the slice.
+/// Nothing is freed when the SmartBuffer is dropped, the elements are only dropped.
+pub struct Overflow<'a, T> {
+    slots: &'a mut [MaybeUninit<T>],
+    in_use: bool,
+}
+
+impl<'a, T> Overflow<'a, T> {
+    /// Wraps the slots. They may hold anything, they are overwritten before they are read.
+    pub fn new(slots: &'a mut [MaybeUninit<T>]) -> Self {
+        Self {
+            slots,
+            in_use: false,
+        }
+    }
+
+    /// Returns the number of slots.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns true if there are no slots.
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+}
+
+unsafe impl<T> Storage<T> for Overflow<'_, T> {
+    fn allocate(&mut self, slots: usize) -> Result<NonNull<T>, TryReserveError> {
+        if self.in_use || slots > self.slots.len() {
+            return Err(TryReserveError::CapacityOverflow)
+        }
+        self.in_use = true;
+        Ok(NonNull::from(&mut *self.slots).cast())
+    }
+
+    unsafe fn deallocate(&mut self, _ptr: NonNull<T>, _slots: usize) {
+        self.in_use = false;
+    }
+
+    unsafe fn grow_in_place(&mut self, _ptr: NonNull<T>, _old_slots: usize, new_slots: usize) -> bool {
+        new_slots <= self.slots.len()
+    }
+
+    fn max_slots(&self) -> usize {
+        self.slots.len()
+    }
+}
+
+/// A SmartBuffer whose overflow region is memory owned by the caller.
+pub type OverflowBuffer<'a, T, const N: usize> = SmartBuffer<T, N, Overflow<'a, T>>;
+
+impl<'a, T, const N: usize> SmartBuffer<T, N, Overflow<'a, T>>
+    where T: Clone
+{
+    /// Creates an empty SmartBuffer that puts the elements past the stack into `overflow`
+    /// instead of the heap, where the default value is set to the value entered. It never
+    /// allocates, so it also works without the `alloc` feature.
+    ///
+    /// The capacity grows up to `N + overflow.len()`. Past that the SmartBuffer behaves as if
+    /// it were out of memory: `push` does nothing and `reserve` panics.
+    ///
+    /// ```
+    /// use smart_buffer::SmartBuffer;
+    /// use core::mem::MaybeUninit;
+    ///
+    /// let mut region = [const { MaybeUninit::<u16>::uninit() }; 64];
+    /// let mut samples = SmartBuffer::<u16, 8, _>::with_overflow(0, &mut region);
+    /// samples.resize(100, 7);
+    /// assert_eq!(samples.len(), 72);
+    /// assert_eq!(samples.heap_len(), 64);
+    /// ```
+    pub fn with_overflow(value: T, overflow: &'a mut [MaybeUninit<T>]) -> Self {
+        Self::new_in(value, 0, Overflow::new(overflow))
+    }
+}
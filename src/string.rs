@@ -0,0 +1,309 @@
+use crate::SmartBuffer;
+use crate::error::TryReserveError;
+use crate::spill::Spill;
+
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::convert::Infallible;
+use core::fmt::{self, Debug, Display, Formatter};
+use core::hash::{Hash, Hasher};
+use core::ops::{Deref, DerefMut};
+use core::str::{self, FromStr, Utf8Error};
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+
+/// A UTF-8 string that stays on the stack up to `N` bytes and moves to the heap beyond that.
+///
+/// The bytes are kept in a `SmartBuffer<u8, N>` with the [`Spill::All`] strategy, so they are
+/// always contiguous and the SmartString derefs to `str`.
+///
+/// It is as large as that SmartBuffer: on 64-bit targets `N` bytes plus 65 bytes for the heap
+/// pointer, size, capacity, spill strategy, read cursor and default byte, rounded up to a
+/// multiple of 8. A `SmartString<23>` takes 88 bytes, the `zeroize`, `stats` and `checkpoint`
+/// features add to that. It is meant for strings that should avoid the allocator, not to be
+/// smaller than a `String`.
+///
+/// ```
+/// use smart_buffer::string::SmartString;
+/// use core::fmt::Write;
+///
+/// let mut id = SmartString::<23>::from("sensor");
+/// write!(id, "-{:04}", 17).unwrap();
+/// assert_eq!(id, "sensor-0017");
+/// assert!(!id.as_buffer().is_spilled());
+///
+/// # #[cfg(feature = "alloc")] {
+/// id.push_str("/a-much-longer-suffix");
+/// assert!(id.as_buffer().is_spilled());
+/// assert!(id.ends_with("suffix"));
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct SmartString<const N:usize>{
+    buf: SmartBuffer<u8,N>,
+}
+
+impl<const N:usize> SmartString<N>{
+    /// Creates an empty SmartString with no heap. Usable in `const` and `static` items.
+    pub const fn new() -> Self{
+        let mut buf = SmartBuffer::new_const(0);
+        buf.spill = Spill::All {unspill_below: None};
+        Self{
+            buf,
+        }
+    }
+
+    /// Creates an empty SmartString that can hold at least `capacity` bytes. Without the `alloc`
+    /// feature it can only ever hold `N` bytes, whatever `capacity` is.
+    pub fn with_capacity(capacity:usize) -> Self{
+        let mut string = Self::new();
+        string.buf.reserve_what_fits(capacity);
+        string
+    }
+
+    /// Turns a byte SmartBuffer into a SmartString, or returns the error if it is not UTF-8.
+    pub fn from_utf8(buf: SmartBuffer<u8,N>) -> Result<Self, Utf8Error>{
+        let buf = buf.with_spill(Spill::All {unspill_below: None});
+        str::from_utf8(contiguous(&buf))?;
+        Ok(Self{
+            buf,
+        })
+    }
+
+    /// Returns the contents as a string slice.
+    pub fn as_str(&self) -> &str{
+        // only whole UTF-8 sequences are ever written
+        unsafe {str::from_utf8_unchecked(contiguous(&self.buf))}
+    }
+
+    /// Returns the contents as a mutable string slice.
+    pub fn as_mut_str(&mut self) -> &mut str{
+        let bytes = match self.buf.as_mut_slices(){
+            (bytes, []) | ([], bytes) => bytes,
+            _ => unreachable!("Spill::All keeps the bytes contiguous"),
+        };
+        unsafe {str::from_utf8_unchecked_mut(bytes)}
+    }
+
+    /// Returns the underlying SmartBuffer.
+    pub fn as_buffer(&self) -> &SmartBuffer<u8,N>{
+        &self.buf
+    }
+
+    /// Consumes the SmartString and returns its bytes.
+    pub fn into_bytes(self) -> SmartBuffer<u8,N>{
+        self.buf
+    }
+
+    /// Returns the number of bytes the SmartString can hold without allocating.
+    pub fn capacity(&self) -> usize{
+        self.buf.capacity()
+    }
+
+    /// Appends a string slice. Without the `alloc` feature, only the characters that fit on the
+    /// stack are appended, so a character is never cut in half.
+    pub fn push_str(&mut self, string: &str){
+        #[cfg(feature = "alloc")]
+        self.buf.reserve(string.len());
+        let mut end = string.len().min(self.buf.capacity() - self.buf.len());
+        while !string.is_char_boundary(end){
+            end -= 1;
+        }
+        self.buf.insert_slice(&string.as_bytes()[..end]);
+    }
+
+    /// Appends a string slice, or returns the error and leaves the SmartString unchanged if there
+    /// is no room for all of it.
+    ///
+    /// ```
+    /// use smart_buffer::string::SmartString;
+    ///
+    /// let mut tag = SmartString::<4>::from("id");
+    /// # #[cfg(not(feature = "alloc"))] {
+    /// assert!(tag.try_push_str("-0017").is_err());
+    /// assert_eq!(tag, "id");
+    /// # }
+    /// assert!(tag.try_push_str("-1").is_ok());
+    /// assert_eq!(tag, "id-1");
+    /// ```
+    pub fn try_push_str(&mut self, string: &str) -> Result<(), TryReserveError>{
+        self.buf.try_reserve(string.len())?;
+        self.buf.insert_slice(string.as_bytes());
+        Ok(())
+    }
+
+    /// Appends a character.
+    pub fn push(&mut self, ch: char){
+        self.push_str(ch.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Removes the last character and returns it.
+    pub fn pop(&mut self) -> Option<char>{
+        let ch = self.as_str().chars().next_back()?;
+        self.buf.truncate(self.buf.len() - ch.len_utf8());
+        Some(ch)
+    }
+
+    /// Shortens the SmartString to `new_len` bytes. Does nothing if `new_len` is not below the
+    /// length, and panics if it is not on a character boundary.
+    pub fn truncate(&mut self, new_len:usize){
+        if new_len < self.buf.len(){
+            assert!(self.as_str().is_char_boundary(new_len), "new_len is not on a character boundary");
+            self.buf.truncate(new_len);
+        }
+    }
+
+    /// Removes all characters, keeping the capacity.
+    pub fn clear(&mut self){
+        self.buf.clear();
+    }
+}
+
+fn contiguous<const N:usize>(buf: &SmartBuffer<u8,N>) -> &[u8]{
+    match buf.as_slices(){
+        (bytes, []) | ([], bytes) => bytes,
+        _ => unreachable!("Spill::All keeps the bytes contiguous"),
+    }
+}
+
+impl<const N:usize> Default for SmartString<N>{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N:usize> Deref for SmartString<N>{
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl<const N:usize> DerefMut for SmartString<N>{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut_str()
+    }
+}
+
+impl<const N:usize> AsRef<str> for SmartString<N>{
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N:usize> AsRef<[u8]> for SmartString<N>{
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl<const N:usize> Borrow<str> for SmartString<N>{
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+/// Without the `alloc` feature, only the characters that fit on the stack are kept, like with
+/// [`push_str`](SmartString::push_str).
+impl<const N:usize> From<&str> for SmartString<N>{
+    fn from(string: &str) -> Self {
+        let mut out = Self::with_capacity(string.len());
+        out.push_str(string);
+        out
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const N:usize> From<String> for SmartString<N>{
+    fn from(string: String) -> Self {
+        Self::from(string.as_str())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const N:usize> From<SmartString<N>> for String{
+    fn from(string: SmartString<N>) -> Self {
+        String::from(string.as_str())
+    }
+}
+
+impl<const N:usize> FromStr for SmartString<N>{
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from(s))
+    }
+}
+
+/// Writing fails with [`fmt::Error`] once a piece does not fit, which can only happen without the
+/// `alloc` feature. The pieces written before it are kept.
+impl<const N:usize> fmt::Write for SmartString<N>{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.try_push_str(s).map_err(|_| fmt::Error)
+    }
+}
+
+impl<const N:usize> Display for SmartString<N>{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(self.as_str(), f)
+    }
+}
+
+impl<const N:usize> Debug for SmartString<N>{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl<const N:usize> Hash for SmartString<N>{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl<const N:usize, const M:usize> PartialEq<SmartString<M>> for SmartString<N>{
+    fn eq(&self, other: &SmartString<M>) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl<const N:usize> Eq for SmartString<N> {}
+
+impl<const N:usize, const M:usize> PartialOrd<SmartString<M>> for SmartString<N>{
+    fn partial_cmp(&self, other: &SmartString<M>) -> Option<Ordering> {
+        self.as_str().partial_cmp(other.as_str())
+    }
+}
+
+impl<const N:usize> Ord for SmartString<N>{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+/// Generates the comparisons with the standard string types in both directions.
+macro_rules! impl_eq_str {
+    ($($(#[$attr:meta])* $ty:ty;)*) => {$(
+        $(#[$attr])*
+        impl<const N:usize> PartialEq<$ty> for SmartString<N>{
+            fn eq(&self, other: &$ty) -> bool {
+                self.as_str() == &other[..]
+            }
+        }
+
+        $(#[$attr])*
+        impl<const N:usize> PartialEq<SmartString<N>> for $ty{
+            fn eq(&self, other: &SmartString<N>) -> bool {
+                &self[..] == other.as_str()
+            }
+        }
+    )*};
+}
+
+impl_eq_str! {
+    str;
+    &str;
+    #[cfg(feature = "alloc")]
+    String;
+}
@@ -0,0 +1,163 @@
+//! Operation-sequence model shared by the differential tests in `tests/model.rs` and the fuzz
+//! targets in `fuzz/`.
+//!
+//! Every operation is applied both to a `SmartBuffer` and to a `Vec` that mirrors every slot up to
+//! the capacity, then the size, the capacity and all slots are compared.
+
+use smart_buffer::spill::Spill;
+use smart_buffer::SmartBuffer;
+
+/// One operation on the buffer. Indices and counts are taken modulo something slightly larger
+/// than the valid range, so out-of-range arguments are exercised too.
+#[derive(Clone, Debug)]
+#[cfg_attr(fuzzing, derive(arbitrary::Arbitrary))]
+pub enum Op {
+    Push(u8),
+    Insert(u8, u8),
+    Remove(u8),
+    Truncate(u8),
+    Shl(u8),
+    Shr(u8),
+    Reserve(u8),
+    ShrinkToFit,
+    Clone,
+    Iterate,
+}
+
+/// The spill strategy the buffer is created with.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(fuzzing, derive(arbitrary::Arbitrary))]
+pub enum Strategy {
+    Split,
+    All,
+    AllUnspillBelow(u8),
+}
+
+impl Strategy {
+    fn spill(self) -> Spill {
+        match self {
+            Strategy::Split => Spill::Split,
+            Strategy::All => Spill::All { unspill_below: None },
+            Strategy::AllUnspillBelow(threshold) => Spill::All { unspill_below: Some(threshold as usize % 8) },
+        }
+    }
+}
+
+/// The reference: `slots` holds every slot up to the capacity, `size` marks the elements.
+struct Model {
+    slots: Vec<String>,
+    size: usize,
+}
+
+impl Model {
+    /// Follows a capacity change. Kept slots keep their values, new ones hold the default.
+    fn resize(&mut self, capacity: usize) {
+        self.slots.resize(capacity, String::new());
+    }
+}
+
+/// Runs `ops` against a `SmartBuffer<String, N>` with an initial capacity of `initial` and the
+/// reference model, panicking on the first difference.
+pub fn run<const N: usize>(strategy: Strategy, initial: u8, ops: &[Op]) {
+    let mut buf = SmartBuffer::<String, N>::default().with_spill(strategy.spill());
+    buf.reserve_exact(initial as usize);
+    let mut model = Model { slots: Vec::new(), size: 0 };
+    model.resize(buf.capacity());
+    check(&buf, &model);
+
+    for op in ops {
+        apply(&mut buf, &mut model, op);
+        check(&buf, &model);
+    }
+}
+
+fn apply<const N: usize>(buf: &mut SmartBuffer<String, N>, model: &mut Model, op: &Op) {
+    let capacity = buf.capacity();
+    match *op {
+        Op::Push(value) => {
+            if model.size < capacity {
+                model.slots[model.size] = value.to_string();
+                model.size += 1;
+            }
+            buf.push(value.to_string());
+        }
+        Op::Insert(value, index) => {
+            let index = index as usize % (capacity + 2);
+            if index < capacity {
+                model.slots[index] = value.to_string();
+                model.size = model.size.max(index + 1);
+            }
+            buf.insert(value.to_string(), index);
+        }
+        Op::Remove(index) => {
+            let index = index as usize % (model.size + 2);
+            let expected = if index < model.size {
+                let removed = model.slots.remove(index);
+                model.slots.insert(model.size - 1, String::new());
+                model.size -= 1;
+                Some(removed)
+            } else {
+                None
+            };
+            assert_eq!(buf.remove(index), expected);
+        }
+        Op::Truncate(len) => {
+            let len = len as usize % (model.size + 2);
+            for slot in model.slots.iter_mut().take(model.size).skip(len) {
+                *slot = String::new();
+            }
+            model.size = model.size.min(len);
+            buf.truncate(len);
+        }
+        Op::Shl(count) => {
+            let count = (count as usize % (capacity + 2)).min(capacity);
+            model.slots.drain(..count);
+            model.resize(capacity);
+            buf.shl(count);
+        }
+        Op::Shr(count) => {
+            let count = (count as usize % (capacity + 2)).min(capacity);
+            model.slots.truncate(capacity - count);
+            model.slots.splice(0..0, (0..count).map(|_| String::new()));
+            buf.shr(count);
+        }
+        Op::Reserve(additional) => {
+            buf.reserve(additional as usize);
+            assert!(buf.capacity() >= model.size + additional as usize);
+        }
+        Op::ShrinkToFit => {
+            buf.shrink_to_fit();
+            assert_eq!(buf.capacity(), model.size.max(N));
+        }
+        Op::Clone => {
+            *buf = buf.clone();
+        }
+        Op::Iterate => {
+            for elem in buf.iter_mut() {
+                elem.push('+');
+            }
+            for slot in &mut model.slots[..model.size] {
+                slot.push('+');
+            }
+            let consumed: Vec<String> = buf.clone().into_iter().collect();
+            assert_eq!(consumed, model.slots[..model.size]);
+            assert_eq!(Vec::from(buf.clone()), model.slots[..model.size]);
+        }
+    }
+    // truncate and remove may unspill, reserve and shrink_to_fit change the capacity
+    model.resize(buf.capacity());
+}
+
+fn check<const N: usize>(buf: &SmartBuffer<String, N>, model: &Model) {
+    assert_eq!(buf.len(), model.size);
+    assert_eq!(buf.capacity(), model.slots.len());
+    for (i, slot) in model.slots.iter().enumerate() {
+        assert_eq!(buf.get(i), Some(slot), "slot {}", i);
+    }
+    assert_eq!(buf.get(model.slots.len()), None);
+    assert!(buf.iter().eq(&model.slots[..model.size]));
+
+    let (stack, heap) = buf.as_slices();
+    assert!(stack.iter().chain(heap).eq(&model.slots[..model.size]));
+    assert!(stack.len() <= N);
+}
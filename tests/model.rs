@@ -0,0 +1,74 @@
+//! Differential tests: random operation sequences are run against both a SmartBuffer and a `Vec`
+//! model, for several stack sizes and every spill strategy. Run them under Miri with
+//! `cargo +nightly miri test --test model` to check the unsafe code as well.
+
+#![cfg(feature = "alloc")]
+
+mod common;
+
+use common::model::{run, Op, Strategy};
+
+/// A small xorshift generator, so the sequences are reproducible without extra dependencies.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn byte(&mut self) -> u8 {
+        self.next() as u8
+    }
+}
+
+fn random_ops(rng: &mut Rng, len: usize) -> Vec<Op> {
+    (0..len)
+        .map(|_| match rng.next() % 16 {
+            0..=4 => Op::Push(rng.byte()),
+            5 | 6 => Op::Insert(rng.byte(), rng.byte() % 32),
+            7 | 8 => Op::Remove(rng.byte()),
+            9 => Op::Truncate(rng.byte() % 32),
+            10 => Op::Shl(rng.byte() % 8),
+            11 => Op::Shr(rng.byte() % 8),
+            12 => Op::Reserve(rng.byte() % 16),
+            13 => Op::ShrinkToFit,
+            14 => Op::Clone,
+            _ => Op::Iterate,
+        })
+        .collect()
+}
+
+fn run_all<const N: usize>(seed: u64) {
+    let (sequences, len) = if cfg!(miri) { (4, 40) } else { (200, 120) };
+    let mut rng = Rng(seed);
+    for strategy in [Strategy::Split, Strategy::All, Strategy::AllUnspillBelow(3)] {
+        for _ in 0..sequences {
+            let initial = rng.byte() % 24;
+            let ops = random_ops(&mut rng, len);
+            run::<N>(strategy, initial, &ops);
+        }
+    }
+}
+
+#[test]
+fn matches_vec_without_stack() {
+    run_all::<0>(0x9e37_79b9_7f4a_7c15);
+}
+
+#[test]
+fn matches_vec_with_one_slot() {
+    run_all::<1>(0xdead_beef_cafe_f00d);
+}
+
+#[test]
+fn matches_vec_with_small_stack() {
+    run_all::<4>(0x0123_4567_89ab_cdef);
+}
+
+#[test]
+fn matches_vec_with_large_stack() {
+    run_all::<16>(0x5555_aaaa_3333_cccc);
+}